@@ -0,0 +1,438 @@
+use std::{
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    os::{
+        fd::AsRawFd,
+        unix::{
+            fs::{FileTypeExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    config, json,
+    totp::{self, Totp},
+};
+
+/// Environment variable with the path of the agent socket, clients use the agent when it is set.
+pub const SOCKET_ENV: &str = "TOTP_CLI_SOCK";
+
+// Clients that do not send their request within this time are disconnected,
+// so a single client can not block the agent.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum AgentError {
+    AlreadyRunning(String),
+    NoSocketPath(String),
+    // The socket path exists, but it is not a socket.
+    NotASocket(String),
+    Response(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                AgentError::AlreadyRunning(message)
+                | AgentError::NoSocketPath(message)
+                | AgentError::NotASocket(message)
+                | AgentError::Response(message) => message,
+            }
+        )
+    }
+}
+
+impl Error for AgentError {}
+
+struct AgentState {
+    // The accounts are dropped from memory when the agent is locked.
+    totps: Option<Vec<Totp>>,
+    last_activity: Instant,
+}
+
+pub struct Agent {
+    config_file_path: PathBuf,
+    digits: u32,
+    interval: u64,
+    lock_timeout: Duration,
+    state: Mutex<AgentState>,
+}
+
+impl Agent {
+    /// Creates an unlocked agent, the accounts are loaded from the secrets file right away.
+    pub fn new(
+        config_file_path: PathBuf,
+        digits: u32,
+        interval: u64,
+        lock_timeout: Duration,
+    ) -> Result<Self, Box<dyn Error>> {
+        let totps = config::load_totps(&config_file_path, digits, interval)?;
+
+        Ok(Self {
+            config_file_path,
+            digits,
+            interval,
+            lock_timeout,
+            state: Mutex::new(AgentState {
+                totps: Some(totps),
+                last_activity: Instant::now(),
+            }),
+        })
+    }
+
+    // The state stays usable when a request panicked while holding the lock,
+    // so one failed request does not take down every request after it.
+    fn state(&self) -> MutexGuard<'_, AgentState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn handle_request(&self, request: &str, time: SystemTime) -> Value {
+        let mut state = self.state();
+        state.last_activity = Instant::now();
+
+        let result = match (request.split_once(' '), request, &state.totps) {
+            (_, "lock", _) => {
+                state.totps = None;
+                Ok(Value::Null)
+            }
+            (_, "unlock", _) => {
+                match config::load_totps(&self.config_file_path, self.digits, self.interval) {
+                    Ok(totps) => {
+                        state.totps = Some(totps);
+                        Ok(Value::Null)
+                    }
+                    Err(err) => Err(err.to_string()),
+                }
+            }
+            (_, "list", Some(totps)) => Ok(json::totps_to_json(totps, time, false)),
+            (Some(("code", name)), _, Some(totps)) => match totp::find_by_name(totps, name) {
+                Some(totp) => Ok(json::totp_to_json(totp, time, false)),
+                None => Err(format!("Could not find an entry named '{name}'.")),
+            },
+            (_, "list", None) | (Some(("code", _)), _, None) => {
+                Err("The agent is locked.".to_string())
+            }
+            _ => Err(format!("Unknown request '{request}'.")),
+        };
+
+        match result {
+            Ok(result) => json!({ "ok": true, "result": result }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        }
+    }
+
+    fn lock_when_inactive(&self) {
+        let mut state = self.state();
+        if state.totps.is_some() && state.last_activity.elapsed() >= self.lock_timeout {
+            state.totps = None;
+        }
+    }
+}
+
+/// The socket path from the environment, otherwise a socket in the user's runtime directory,
+/// which is only accessible by the user.
+pub fn socket_path() -> Result<PathBuf, AgentError> {
+    if let Some(socket_path) = env::var_os(SOCKET_ENV) {
+        return Ok(PathBuf::from(socket_path));
+    }
+
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(|runtime_dir| Path::new(&runtime_dir).join("totp-cli.sock"))
+        .ok_or_else(|| {
+            AgentError::NoSocketPath(format!(
+                "Could not find a path for the agent socket, set '{SOCKET_ENV}' or 'XDG_RUNTIME_DIR'."
+            ))
+        })
+}
+
+/// Binds the socket, only the user is allowed to connect to it.
+/// A socket left behind by an agent that is no longer running is removed,
+/// any other file at the path is left alone and results in an error.
+pub fn bind(socket_path: &Path) -> Result<UnixListener, Box<dyn Error>> {
+    if let Ok(metadata) = fs::symlink_metadata(socket_path) {
+        if !metadata.file_type().is_socket() {
+            return Err(AgentError::NotASocket(format!(
+                "'{}' already exists and is not a socket.",
+                socket_path.to_string_lossy()
+            ))
+            .into());
+        }
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(AgentError::AlreadyRunning(format!(
+                "An agent is already running on '{}'.",
+                socket_path.to_string_lossy()
+            ))
+            .into());
+        }
+        fs::remove_file(socket_path)?;
+    }
+
+    // Bound under a umask that keeps everyone else out, so the socket is never accessible
+    // to other users, not even before its permissions are set.
+    // SAFETY: umask only takes an integer and always succeeds.
+    let previous_umask = unsafe { libc::umask(0o077) };
+    let listener = UnixListener::bind(socket_path);
+    // SAFETY: see above.
+    unsafe { libc::umask(previous_umask) };
+    let listener = listener?;
+    fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+/// Moves the agent to the background the same way as ssh-agent, the calling process exits
+/// and the agent continues in a child process without a terminal.
+/// The standard streams are closed, so a shell evaluating the output does not wait for the agent.
+/// Has to be called before any threads are started.
+pub fn daemonize() -> io::Result<()> {
+    // SAFETY: there is only one thread, so the child does not inherit locks held by other threads.
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        _ => process::exit(0),
+    }
+
+    // SAFETY: setsid has no arguments, it only fails when the process already leads a session.
+    unsafe { libc::setsid() };
+    env::set_current_dir("/")?;
+
+    let dev_null = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: both file descriptors are open for the duration of the call.
+        if unsafe { libc::dup2(dev_null.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers requests until the listener fails, requests are handled one at a time.
+pub fn serve(listener: &UnixListener, agent: &Arc<Agent>) -> io::Result<()> {
+    let lock_agent = Arc::clone(agent);
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        lock_agent.lock_when_inactive();
+    });
+
+    for stream in listener.incoming() {
+        // A client failing is not a reason to stop the agent.
+        let _ = handle_client(&stream?, agent);
+    }
+
+    Ok(())
+}
+
+fn handle_client(stream: &UnixStream, agent: &Agent) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut request = String::new();
+    BufReader::new(stream).read_line(&mut request)?;
+
+    // A request that panics fails on its own, the agent keeps answering other requests.
+    let response = panic::catch_unwind(AssertUnwindSafe(|| {
+        agent.handle_request(request.trim_end_matches(['\n', '\r']), SystemTime::now())
+    }))
+    .unwrap_or_else(|_| json!({ "ok": false, "error": "The agent failed to handle the request." }));
+
+    let mut stream = stream;
+    writeln!(stream, "{response}")
+}
+
+/// Sends the request to the agent and returns the result, a failed request results in an error.
+pub fn request(socket_path: &Path, request: &str) -> Result<Value, Box<dyn Error>> {
+    let mut stream = UnixStream::connect(socket_path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!(
+                "Could not connect to the agent on '{}': {err}.",
+                socket_path.to_string_lossy()
+            ),
+        )
+    })?;
+    writeln!(stream, "{request}")?;
+
+    let mut response = String::new();
+    BufReader::new(&stream).read_line(&mut response)?;
+    if response.is_empty() {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "The agent did not respond.").into());
+    }
+
+    let mut response: Value = serde_json::from_str(&response)?;
+    if response["ok"] == json!(true) {
+        Ok(response["result"].take())
+    } else {
+        Err(AgentError::Response(
+            response["error"]
+                .as_str()
+                .unwrap_or("The agent failed without an error message.")
+                .to_string(),
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "// Comment
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+Otpauth://totp/Widget Co:me@my-domain.com?secret=MFRGGZD&issuer=WidgetCo
+";
+
+    // Starts an agent on a socket in a temporary directory, the agent runs until the test ends.
+    fn start_agent(directory: &tempfile::TempDir, lock_timeout: Duration) -> PathBuf {
+        let config_file_path = directory.path().join("totp.txt");
+        fs::write(&config_file_path, CONTENT).unwrap();
+
+        let socket_path = directory.path().join("agent.sock");
+        let listener = bind(&socket_path).unwrap();
+        let agent = Arc::new(Agent::new(config_file_path, 6, 30, lock_timeout).unwrap());
+
+        thread::spawn(move || serve(&listener, &agent));
+
+        socket_path
+    }
+
+    #[test]
+    fn can_list_entries() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+
+        let result = request(&socket_path, "list").unwrap();
+
+        assert_eq!(json!("Acme Inc."), result[0]["name"]);
+        assert_eq!(json!("Widget Co"), result[1]["name"]);
+        // Secrets never leave the agent.
+        assert_eq!(Value::Null, result[0]["secret"]);
+    }
+
+    #[test]
+    fn can_get_code() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+
+        let result = request(&socket_path, "code widget co").unwrap();
+
+        assert_eq!(json!("Widget Co"), result["name"]);
+        assert_eq!(6, result["code"].as_str().unwrap().len());
+
+        assert!(matches!(
+            request(&socket_path, "code Gizmo Corporation"),
+            Err(err) if err.to_string() == "Could not find an entry named 'Gizmo Corporation'."
+        ));
+    }
+
+    #[test]
+    fn socket_is_only_accessible_by_the_user() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+
+        assert_eq!(
+            0o600,
+            fs::metadata(socket_path).unwrap().permissions().mode() & 0o777
+        );
+    }
+
+    #[test]
+    fn second_agent_can_not_use_socket_of_running_agent() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+
+        assert!(bind(&socket_path).is_err());
+    }
+
+    #[test]
+    fn stale_socket_is_replaced_but_other_files_are_kept() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = directory.path().join("agent.sock");
+        drop(bind(&socket_path).unwrap());
+
+        assert!(bind(&socket_path).is_ok());
+
+        let file_path = directory.path().join("notes.txt");
+        fs::write(&file_path, "notes").unwrap();
+        assert!(matches!(
+            bind(&file_path),
+            Err(err) if err.to_string().ends_with("already exists and is not a socket.")
+        ));
+        assert_eq!("notes", fs::read_to_string(&file_path).unwrap());
+    }
+
+    #[test]
+    fn locked_agent_refuses_requests_until_unlocked() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+
+        request(&socket_path, "lock").unwrap();
+        assert!(matches!(
+            request(&socket_path, "list"),
+            Err(err) if err.to_string() == "The agent is locked."
+        ));
+
+        request(&socket_path, "unlock").unwrap();
+        assert!(request(&socket_path, "list").is_ok());
+    }
+
+    #[test]
+    fn agent_locks_after_inactivity() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_millis(100));
+
+        thread::sleep(Duration::from_millis(1500));
+
+        assert!(matches!(
+            request(&socket_path, "code Acme Inc."),
+            Err(err) if err.to_string() == "The agent is locked."
+        ));
+    }
+
+    #[test]
+    fn invalid_secrets_are_refused_when_loading() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+        let config_file_path = directory.path().join("totp.txt");
+        fs::write(
+            &config_file_path,
+            "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=abcd efgh 1234&issuer=AcmeCorp",
+        )
+        .unwrap();
+
+        assert!(Agent::new(config_file_path, 6, 30, Duration::from_mins(1)).is_err());
+        assert!(matches!(
+            request(&socket_path, "unlock"),
+            Err(err) if err.to_string().ends_with("is not valid base32.")
+        ));
+        // The entries that were loaded before are still served.
+        assert!(request(&socket_path, "code Acme Inc.").is_ok());
+    }
+
+    #[test]
+    fn unknown_request_results_in_error() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = start_agent(&directory, Duration::from_mins(1));
+
+        assert!(matches!(
+            request(&socket_path, "delete Acme Inc."),
+            Err(err) if err.to_string() == "Unknown request 'delete Acme Inc.'."
+        ));
+    }
+}
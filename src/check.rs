@@ -0,0 +1,353 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+
+use crate::{
+    config::{self, TotpSecretFileError},
+    document::{Document, Line},
+    permissions,
+};
+
+// RFC 4226 recommends 160 bits, but most providers hand out 80 bit secrets,
+// so only secrets shorter than that are reported.
+const MIN_SECRET_BITS: usize = 80;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // Diagnostics about the file as a whole has no line.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, line: Option<usize>, message: String) -> Self {
+        Self {
+            severity,
+            line,
+            message,
+        }
+    }
+}
+
+pub fn check_file(
+    config_file_path: &PathBuf,
+    digits: u32,
+    interval: u64,
+) -> Result<Vec<Diagnostic>, TotpSecretFileError> {
+    let content = config::read_config_file(config_file_path)?;
+
+    let mut diagnostics = vec![];
+
+    diagnostics.extend(check_permissions(config_file_path));
+
+    diagnostics.extend(check_content(&content, digits, interval));
+
+    Ok(diagnostics)
+}
+
+pub fn check_content(content: &str, digits: u32, interval: u64) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    if let Some(line) = content.split('\n').position(|x| x.ends_with('\r')) {
+        diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            Some(line + 1),
+            "File uses Windows line endings (CRLF), please convert it to Unix line endings (LF)."
+                .to_string(),
+        ));
+    }
+
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut secrets: HashMap<&[u8], usize> = HashMap::new();
+
+    let document = Document::parse(content, digits, interval);
+    for (index, document_line) in document.lines().iter().enumerate() {
+        let line = index + 1;
+        let (entry, totp) = match document_line {
+            Line::Entry { raw, totp } => (raw, totp),
+            Line::Unknown { error, .. } => {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    Some(line),
+                    error.to_string(),
+                ));
+                continue;
+            }
+            Line::Comment(_) | Line::Blank => continue,
+        };
+
+        for (key, _) in config::query_parameters(entry) {
+            if !config::SUPPORTED_PARAMETERS.contains(&key.to_lowercase().as_str()) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    Some(line),
+                    format!("Unsupported parameter '{key}' is ignored."),
+                ));
+            }
+        }
+
+        if let Some(first_line) = names.get(&totp.name) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                Some(line),
+                format!(
+                    "Duplicate name '{}', first used on line {first_line}.",
+                    totp.name
+                ),
+            ));
+        } else {
+            names.insert(totp.name.clone(), line);
+        }
+
+        let Some(secret) = totp.decoded_secret() else {
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                Some(line),
+                format!("The secret for '{}' is not valid base32.", totp.name),
+            ));
+            continue;
+        };
+
+        let secret_bits = secret.len() * 8;
+        if secret_bits < MIN_SECRET_BITS {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                Some(line),
+                format!(
+                    "The secret for '{}' is only {secret_bits} bits, expected at least {MIN_SECRET_BITS} bits.",
+                    totp.name
+                ),
+            ));
+        }
+
+        if let Some(first_line) = secrets.get(&secret) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                Some(line),
+                format!(
+                    "The secret for '{}' is a duplicate of the secret on line {first_line}.",
+                    totp.name
+                ),
+            ));
+        } else {
+            secrets.insert(secret, line);
+        }
+    }
+
+    diagnostics
+}
+
+// The same checks as every other command, which refuses to read the file when they fail.
+fn check_permissions(config_file_path: &Path) -> Option<Diagnostic> {
+    permissions::check(config_file_path)
+        .err()
+        .map(|err| Diagnostic::new(Severity::Error, None, err.to_string()))
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|x| x.severity == Severity::Error)
+}
+
+pub fn format_diagnostics(config_file_path: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut lines = diagnostics
+        .iter()
+        .map(|diagnostic| match diagnostic.line {
+            Some(line) => format!(
+                "{config_file_path}:{line}: {}: {}",
+                diagnostic.severity, diagnostic.message
+            ),
+            None => format!(
+                "{config_file_path}: {}: {}",
+                diagnostic.severity, diagnostic.message
+            ),
+        })
+        .collect::<Vec<_>>();
+
+    let errors = diagnostics
+        .iter()
+        .filter(|x| x.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+
+    lines.push(format!(
+        "{config_file_path}: {errors} error(s), {warnings} warning(s)."
+    ));
+
+    lines.join("\n")
+}
+
+pub fn format_diagnostics_json(config_file_path: &str, diagnostics: &[Diagnostic]) -> String {
+    json!({
+        "file": config_file_path,
+        "diagnostics": diagnostics
+            .iter()
+            .map(|diagnostic| json!({
+                "severity": diagnostic.severity.to_string(),
+                "line": diagnostic.line,
+                "message": diagnostic.message,
+            }))
+            .collect::<Vec<_>>(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<(Severity, Option<usize>)> {
+        diagnostics.iter().map(|x| (x.severity, x.line)).collect()
+    }
+
+    #[test]
+    fn valid_content_has_no_diagnostics() {
+        let content = "// Big companies
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+
+// Small companies
+Otpauth://totp/Widget Co:me@my-domain.com?secret=JXQWZ4TVRNUP5YKM&issuer=WidgetCo
+";
+
+        assert_eq!(Vec::<Diagnostic>::new(), check_content(content, 6, 30));
+    }
+
+    #[test]
+    fn invalid_lines_are_reported_as_errors() {
+        let content =
+            "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+qwerty
+Otpauth://totp/Widget Co:me@my-domain.com?secret=JXQWZ4TVRNUP5YK!&issuer=WidgetCo";
+
+        assert_eq!(
+            vec![(Severity::Error, Some(2)), (Severity::Error, Some(3))],
+            messages(&check_content(content, 6, 30))
+        );
+    }
+
+    #[test]
+    fn short_and_duplicate_secrets_and_names_are_reported_as_warnings() {
+        let content =
+            "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=JXQWZ4TVRNUP5YKM&issuer=AcmeCorp
+Otpauth://totp/Widget Co:me@my-domain.com?secret=gzmwv5jlomni2xjl&issuer=WidgetCo
+Otpauth://totp/Foobar Inc.:me@my-domain.com?secret=MFRGGZD&issuer=FoobarInc";
+
+        let diagnostics = check_content(content, 6, 30);
+
+        assert_eq!(
+            vec![
+                (Severity::Warning, Some(2)),
+                (Severity::Warning, Some(3)),
+                (Severity::Warning, Some(4))
+            ],
+            messages(&diagnostics)
+        );
+        assert!(diagnostics[0].message.contains("first used on line 1"));
+        assert!(diagnostics[1].message.contains("secret on line 1"));
+        assert!(diagnostics[2].message.contains("only 32 bits"));
+    }
+
+    #[test]
+    fn unsupported_parameters_are_reported_as_warnings() {
+        let content = "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp&period=60";
+
+        let diagnostics = check_content(content, 6, 30);
+
+        assert_eq!(vec![(Severity::Warning, Some(1))], messages(&diagnostics));
+        assert!(diagnostics[0].message.contains("'period'"));
+    }
+
+    #[test]
+    fn windows_line_endings_are_reported() {
+        let content = "// Comment\r
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp\r
+";
+
+        assert_eq!(
+            vec![(Severity::Warning, Some(1))],
+            messages(&check_content(content, 6, 30))
+        );
+    }
+
+    #[test]
+    fn group_and_world_accessible_permissions_are_reported() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("totp.txt");
+        fs::write(&path, "").unwrap();
+        let assertions = [
+            (0o600, 0o700, false),
+            (0o400, 0o700, false),
+            (0o700, 0o755, false),
+            (0o640, 0o700, true),
+            (0o604, 0o700, true),
+            (0o666, 0o700, true),
+            (0o600, 0o777, true),
+            (0o600, 0o1777, false),
+        ];
+
+        for (file_mode, directory_mode, expected) in assertions {
+            fs::set_permissions(&path, fs::Permissions::from_mode(file_mode)).unwrap();
+            fs::set_permissions(directory.path(), fs::Permissions::from_mode(directory_mode))
+                .unwrap();
+            assert_eq!(expected, check_permissions(&path).is_some());
+        }
+        fs::set_permissions(directory.path(), fs::Permissions::from_mode(0o700)).unwrap();
+    }
+
+    #[test]
+    fn can_check_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp"
+        )
+        .unwrap();
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o644)).unwrap();
+
+        let diagnostics = check_file(&file.path().to_path_buf(), 6, 30).unwrap();
+
+        assert_eq!(vec![(Severity::Error, None)], messages(&diagnostics));
+    }
+
+    #[test]
+    fn diagnostics_are_formatted_correctly() {
+        let diagnostics = [
+            Diagnostic::new(Severity::Warning, None, "Bad permissions.".to_string()),
+            Diagnostic::new(Severity::Error, Some(3), "Bad line.".to_string()),
+        ];
+
+        assert_eq!(
+            "totp.txt: warning: Bad permissions.
+totp.txt:3: error: Bad line.
+totp.txt: 1 error(s), 1 warning(s).",
+            format_diagnostics("totp.txt", &diagnostics)
+        );
+
+        assert_eq!(
+            r#"{"diagnostics":[{"line":null,"message":"Bad permissions.","severity":"warning"},{"line":3,"message":"Bad line.","severity":"error"}],"file":"totp.txt"}"#,
+            format_diagnostics_json("totp.txt", &diagnostics)
+        );
+    }
+}
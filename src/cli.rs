@@ -0,0 +1,766 @@
+use std::{error::Error, fmt};
+
+use crate::{exec, menu::MenuAction, watch::WatchFormat, INTERVAL};
+
+const DEFAULT_CONFIG_FILE_NAME: &str = "totp.txt";
+const DEFAULT_LOCK_TIMEOUT: u64 = 15 * 60;
+const DEFAULT_MIN_REMAINING: u64 = 5;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AgentAction {
+    Start,
+    Lock,
+    Unlock,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Command {
+    Tui {
+        config_file_name: String,
+        // The codes are masked until they are revealed.
+        private: bool,
+    },
+    Check {
+        config_file_name: String,
+        json: bool,
+    },
+    List {
+        config_file_name: String,
+        json: bool,
+        include_secrets: bool,
+    },
+    Code {
+        config_file_name: String,
+        name: String,
+        json: bool,
+        include_secrets: bool,
+        copy: bool,
+    },
+    Menu {
+        config_file_name: String,
+        launcher: Option<String>,
+        action: MenuAction,
+    },
+    Watch {
+        config_file_name: String,
+        name: String,
+        format: WatchFormat,
+    },
+    Agent {
+        config_file_name: String,
+        socket: Option<String>,
+        lock_timeout: u64,
+        // The agent stays in the foreground instead of moving to the background.
+        foreground: bool,
+        action: AgentAction,
+    },
+    Exec {
+        config_file_name: String,
+        name: String,
+        variable: String,
+        min_remaining: u64,
+        command: Vec<String>,
+    },
+    FixPerms {
+        config_file_name: String,
+    },
+}
+
+impl Command {
+    pub fn config_file_name(&self) -> &str {
+        match self {
+            Command::Tui {
+                config_file_name, ..
+            }
+            | Command::Check {
+                config_file_name, ..
+            }
+            | Command::List {
+                config_file_name, ..
+            }
+            | Command::Code {
+                config_file_name, ..
+            }
+            | Command::Menu {
+                config_file_name, ..
+            }
+            | Command::Watch {
+                config_file_name, ..
+            }
+            | Command::Agent {
+                config_file_name, ..
+            }
+            | Command::Exec {
+                config_file_name, ..
+            }
+            | Command::FixPerms { config_file_name } => config_file_name,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum CliError {
+    UnknownOption(String),
+    UnexpectedArgument(String),
+    MissingArgument(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(option) => write!(f, "Unknown option '{option}'."),
+            CliError::UnexpectedArgument(argument) => {
+                write!(f, "Unexpected argument '{argument}'.")
+            }
+            CliError::MissingArgument(argument) => write!(f, "Missing argument '{argument}'."),
+            CliError::MissingValue(option) => write!(f, "Missing value for option '{option}'."),
+            CliError::InvalidValue(option, value) => {
+                write!(f, "Invalid value '{value}' for option '{option}'.")
+            }
+        }
+    }
+}
+
+impl Error for CliError {}
+
+// The options of a command, split into flags, options with a value and positional arguments.
+struct Options<'a> {
+    flags: Vec<&'a str>,
+    values: Vec<(&'a str, &'a str)>,
+    positionals: Vec<&'a str>,
+}
+
+impl<'a> Options<'a> {
+    fn parse(
+        args: &[&'a str],
+        flags: &[&str],
+        value_options: &[&str],
+    ) -> Result<Options<'a>, CliError> {
+        let mut options = Options {
+            flags: vec![],
+            values: vec![],
+            positionals: vec![],
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if flags.contains(arg) {
+                options.flags.push(arg);
+            } else if value_options.contains(arg) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::MissingValue((*arg).to_string()))?;
+                options.values.push((arg, value));
+            } else if arg.starts_with("--") {
+                return Err(CliError::UnknownOption((*arg).to_string()));
+            } else {
+                options.positionals.push(arg);
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    fn value(&self, option: &str) -> Option<&'a str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(key, _)| *key == option)
+            .map(|(_, value)| *value)
+    }
+
+    fn number_value(&self, option: &str, default: u64) -> Result<u64, CliError> {
+        match self.value(option) {
+            None => Ok(default),
+            Some(value) => value
+                .parse()
+                .map_err(|_| CliError::InvalidValue(option.to_string(), value.to_string())),
+        }
+    }
+
+    // Commands working on a single entry take its name as the first positional argument.
+    fn take_name(&mut self) -> Result<String, CliError> {
+        if self.positionals.is_empty() {
+            return Err(CliError::MissingArgument("name".to_string()));
+        }
+
+        Ok(self.positionals.remove(0).to_string())
+    }
+
+    // The name of the secrets file is given with '--file',
+    // or as the only positional argument if the command allows it.
+    fn config_file_name(&self, allow_positional: bool) -> Result<String, CliError> {
+        match (self.value("--file"), self.positionals.as_slice()) {
+            (Some(file_name), []) => Ok(file_name.to_string()),
+            (None, []) => Ok(DEFAULT_CONFIG_FILE_NAME.to_string()),
+            (None, [file_name]) if allow_positional => Ok((*file_name).to_string()),
+            (_, [.., unexpected]) => Err(CliError::UnexpectedArgument((*unexpected).to_string())),
+        }
+    }
+}
+
+/// Removes the '--insecure' option, which every command takes, from the arguments.
+/// With it, secrets files other users can access are read anyway.
+/// Arguments after '--' are the command of 'exec' and are kept as they are.
+pub fn take_insecure_option(args: &mut Vec<String>) -> bool {
+    let end = args.iter().position(|x| x == "--").unwrap_or(args.len());
+    let Some(index) = args[..end].iter().position(|x| x == "--insecure") else {
+        return false;
+    };
+
+    args.remove(index);
+    // The option can be given more than once.
+    take_insecure_option(args);
+
+    true
+}
+
+/// Parses the command line arguments, the first argument is expected to be the program name.
+/// If the first argument is not a known command, it is the name of the TOTP secrets file,
+/// this way `totp-cli "my_other_totp_secrets_file.txt"` keeps working.
+pub fn parse_args(args: &[String]) -> Result<Command, CliError> {
+    let args = args.iter().skip(1).map(String::as_str).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        ["check", rest @ ..] => {
+            let options = Options::parse(rest, &["--json"], &["--file"])?;
+
+            Ok(Command::Check {
+                config_file_name: options.config_file_name(true)?,
+                json: options.flag("--json"),
+            })
+        }
+        ["list", rest @ ..] => {
+            let options = Options::parse(rest, &["--json", "--include-secrets"], &["--file"])?;
+
+            Ok(Command::List {
+                config_file_name: options.config_file_name(false)?,
+                json: options.flag("--json"),
+                include_secrets: options.flag("--include-secrets"),
+            })
+        }
+        ["code", rest @ ..] => {
+            let mut options = Options::parse(
+                rest,
+                &["--json", "--include-secrets", "--copy"],
+                &["--file"],
+            )?;
+            let name = options.take_name()?;
+
+            Ok(Command::Code {
+                config_file_name: options.config_file_name(false)?,
+                name,
+                json: options.flag("--json"),
+                include_secrets: options.flag("--include-secrets"),
+                copy: options.flag("--copy"),
+            })
+        }
+        ["menu", rest @ ..] => {
+            let options = Options::parse(rest, &[], &["--file", "--launcher", "--action"])?;
+            let action = match options.value("--action") {
+                None | Some("copy") => MenuAction::Copy,
+                Some("type") => MenuAction::Type,
+                Some("print") => MenuAction::Print,
+                Some(value) => {
+                    return Err(CliError::InvalidValue(
+                        "--action".to_string(),
+                        value.to_string(),
+                    ))
+                }
+            };
+
+            Ok(Command::Menu {
+                config_file_name: options.config_file_name(false)?,
+                launcher: options.value("--launcher").map(ToString::to_string),
+                action,
+            })
+        }
+        ["watch", rest @ ..] => {
+            let mut options = Options::parse(rest, &[], &["--file", "--format"])?;
+            let name = options.take_name()?;
+            let format = match options.value("--format") {
+                None | Some("plain" | "polybar" | "i3blocks") => WatchFormat::Plain,
+                Some("waybar") => WatchFormat::Waybar,
+                Some(value) => {
+                    return Err(CliError::InvalidValue(
+                        "--format".to_string(),
+                        value.to_string(),
+                    ))
+                }
+            };
+
+            Ok(Command::Watch {
+                config_file_name: options.config_file_name(false)?,
+                name,
+                format,
+            })
+        }
+        ["fix-perms", rest @ ..] => {
+            let options = Options::parse(rest, &[], &["--file"])?;
+
+            Ok(Command::FixPerms {
+                config_file_name: options.config_file_name(true)?,
+            })
+        }
+        ["agent", rest @ ..] => parse_agent_args(rest),
+        ["exec", rest @ ..] => parse_exec_args(rest),
+        rest => {
+            let options = Options::parse(rest, &["--private"], &["--file"])?;
+
+            Ok(Command::Tui {
+                config_file_name: options.config_file_name(true)?,
+                private: options.flag("--private"),
+            })
+        }
+    }
+}
+
+fn parse_agent_args(rest: &[&str]) -> Result<Command, CliError> {
+    let mut options = Options::parse(
+        rest,
+        &["--foreground"],
+        &["--file", "--socket", "--lock-timeout"],
+    )?;
+    let action = match options.positionals.first() {
+        Some(&"lock") => AgentAction::Lock,
+        Some(&"unlock") => AgentAction::Unlock,
+        _ => AgentAction::Start,
+    };
+    if action != AgentAction::Start {
+        options.positionals.remove(0);
+    }
+    let lock_timeout = options.number_value("--lock-timeout", DEFAULT_LOCK_TIMEOUT)?;
+
+    Ok(Command::Agent {
+        config_file_name: options.config_file_name(false)?,
+        socket: options.value("--socket").map(ToString::to_string),
+        lock_timeout,
+        foreground: options.flag("--foreground"),
+        action,
+    })
+}
+
+fn parse_exec_args(rest: &[&str]) -> Result<Command, CliError> {
+    // Everything after '--' is the command, so its options are not parsed as our own.
+    let (rest, command) = match rest.iter().position(|x| *x == "--") {
+        Some(index) => (&rest[..index], &rest[index + 1..]),
+        None => (rest, &[][..]),
+    };
+    if command.is_empty() {
+        return Err(CliError::MissingArgument("command".to_string()));
+    }
+
+    let mut options = Options::parse(rest, &[], &["--file", "--env", "--min-remaining"])?;
+    let name = options.take_name()?;
+    let min_remaining = options.number_value("--min-remaining", DEFAULT_MIN_REMAINING)?;
+    // No code ever has that many seconds remaining, so the command would never run.
+    if min_remaining >= INTERVAL {
+        return Err(CliError::InvalidValue(
+            "--min-remaining".to_string(),
+            min_remaining.to_string(),
+        ));
+    }
+
+    Ok(Command::Exec {
+        config_file_name: options.config_file_name(false)?,
+        name,
+        variable: options
+            .value("--env")
+            .unwrap_or(exec::DEFAULT_CODE_VARIABLE)
+            .to_string(),
+        min_remaining,
+        command: command.iter().map(ToString::to_string).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        ["totp-cli"]
+            .iter()
+            .chain(args)
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn can_parse_tui_command() {
+        let assertions = [
+            (
+                args(&[]),
+                Command::Tui {
+                    config_file_name: "totp.txt".to_string(),
+                    private: false,
+                },
+            ),
+            (
+                args(&["my_other_totp_secrets_file.txt"]),
+                Command::Tui {
+                    config_file_name: "my_other_totp_secrets_file.txt".to_string(),
+                    private: false,
+                },
+            ),
+            (
+                args(&["--file", "my_other_totp_secrets_file.txt"]),
+                Command::Tui {
+                    config_file_name: "my_other_totp_secrets_file.txt".to_string(),
+                    private: false,
+                },
+            ),
+            (
+                args(&["--private", "work.txt"]),
+                Command::Tui {
+                    config_file_name: "work.txt".to_string(),
+                    private: true,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_fix_perms_command() {
+        assert_eq!(
+            Ok(Command::FixPerms {
+                config_file_name: "work.txt".to_string(),
+            }),
+            parse_args(&args(&["fix-perms", "work.txt"]))
+        );
+    }
+
+    #[test]
+    fn insecure_option_is_taken_from_any_command() {
+        let mut input = args(&["list", "--insecure", "--json"]);
+        assert!(take_insecure_option(&mut input));
+        assert_eq!(args(&["list", "--json"]), input);
+
+        let mut input = args(&["--insecure", "--insecure"]);
+        assert!(take_insecure_option(&mut input));
+        assert_eq!(args(&[]), input);
+
+        // The option is left alone in the command of 'exec'.
+        let mut input = args(&["exec", "Acme", "--", "deploy", "--insecure"]);
+        assert!(!take_insecure_option(&mut input));
+        assert_eq!(args(&["exec", "Acme", "--", "deploy", "--insecure"]), input);
+    }
+
+    #[test]
+    fn can_parse_check_command() {
+        let assertions = [
+            (
+                args(&["check"]),
+                Command::Check {
+                    config_file_name: "totp.txt".to_string(),
+                    json: false,
+                },
+            ),
+            (
+                args(&["check", "work.txt"]),
+                Command::Check {
+                    config_file_name: "work.txt".to_string(),
+                    json: false,
+                },
+            ),
+            (
+                args(&["check", "--json", "work.txt"]),
+                Command::Check {
+                    config_file_name: "work.txt".to_string(),
+                    json: true,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_list_command() {
+        let assertions = [
+            (
+                args(&["list"]),
+                Command::List {
+                    config_file_name: "totp.txt".to_string(),
+                    json: false,
+                    include_secrets: false,
+                },
+            ),
+            (
+                args(&["list", "--json", "--include-secrets", "--file", "work.txt"]),
+                Command::List {
+                    config_file_name: "work.txt".to_string(),
+                    json: true,
+                    include_secrets: true,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_code_command() {
+        let assertions = [
+            (
+                args(&["code", "Acme Inc."]),
+                Command::Code {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    json: false,
+                    include_secrets: false,
+                    copy: false,
+                },
+            ),
+            (
+                args(&["code", "--json", "Acme Inc.", "--file", "work.txt"]),
+                Command::Code {
+                    config_file_name: "work.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    json: true,
+                    include_secrets: false,
+                    copy: false,
+                },
+            ),
+            (
+                args(&["code", "Acme Inc.", "--copy"]),
+                Command::Code {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    json: false,
+                    include_secrets: false,
+                    copy: true,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_menu_command() {
+        let assertions = [
+            (
+                args(&["menu"]),
+                Command::Menu {
+                    config_file_name: "totp.txt".to_string(),
+                    launcher: None,
+                    action: MenuAction::Copy,
+                },
+            ),
+            (
+                args(&["menu", "--launcher", "rofi", "--action", "type"]),
+                Command::Menu {
+                    config_file_name: "totp.txt".to_string(),
+                    launcher: Some("rofi".to_string()),
+                    action: MenuAction::Type,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_watch_command() {
+        let assertions = [
+            (
+                args(&["watch", "Acme Inc."]),
+                Command::Watch {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    format: WatchFormat::Plain,
+                },
+            ),
+            (
+                args(&["watch", "Acme Inc.", "--format", "waybar"]),
+                Command::Watch {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    format: WatchFormat::Waybar,
+                },
+            ),
+            (
+                args(&["watch", "Acme Inc.", "--format", "i3blocks"]),
+                Command::Watch {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    format: WatchFormat::Plain,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_agent_command() {
+        let assertions = [
+            (
+                args(&["agent"]),
+                Command::Agent {
+                    config_file_name: "totp.txt".to_string(),
+                    socket: None,
+                    lock_timeout: 900,
+                    foreground: false,
+                    action: AgentAction::Start,
+                },
+            ),
+            (
+                args(&[
+                    "agent",
+                    "--socket",
+                    "/tmp/agent.sock",
+                    "--lock-timeout",
+                    "60",
+                    "--foreground",
+                ]),
+                Command::Agent {
+                    config_file_name: "totp.txt".to_string(),
+                    socket: Some("/tmp/agent.sock".to_string()),
+                    lock_timeout: 60,
+                    foreground: true,
+                    action: AgentAction::Start,
+                },
+            ),
+            (
+                args(&["agent", "lock"]),
+                Command::Agent {
+                    config_file_name: "totp.txt".to_string(),
+                    socket: None,
+                    lock_timeout: 900,
+                    foreground: false,
+                    action: AgentAction::Lock,
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn can_parse_exec_command() {
+        let assertions = [
+            (
+                args(&["exec", "Acme Inc.", "--", "vpn", "--otp", "{code}"]),
+                Command::Exec {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    variable: "TOTP_CODE".to_string(),
+                    min_remaining: 5,
+                    command: vec!["vpn".to_string(), "--otp".to_string(), "{code}".to_string()],
+                },
+            ),
+            (
+                args(&[
+                    "exec",
+                    "Acme Inc.",
+                    "--env",
+                    "VPN_OTP",
+                    "--min-remaining",
+                    "10",
+                    "--",
+                    "vpn",
+                    "--file",
+                    "vpn.conf",
+                ]),
+                Command::Exec {
+                    config_file_name: "totp.txt".to_string(),
+                    name: "Acme Inc.".to_string(),
+                    variable: "VPN_OTP".to_string(),
+                    min_remaining: 10,
+                    command: vec![
+                        "vpn".to_string(),
+                        "--file".to_string(),
+                        "vpn.conf".to_string(),
+                    ],
+                },
+            ),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(Ok(expected), parse_args(&input));
+        }
+    }
+
+    #[test]
+    fn invalid_arguments_results_in_error() {
+        assert_eq!(
+            Err(CliError::UnknownOption("--yaml".to_string())),
+            parse_args(&args(&["check", "--yaml"]))
+        );
+        assert_eq!(
+            Err(CliError::UnexpectedArgument("second.txt".to_string())),
+            parse_args(&args(&["check", "first.txt", "second.txt"]))
+        );
+        assert_eq!(
+            Err(CliError::UnexpectedArgument("second.txt".to_string())),
+            parse_args(&args(&["first.txt", "second.txt"]))
+        );
+        assert_eq!(
+            Err(CliError::UnexpectedArgument("work.txt".to_string())),
+            parse_args(&args(&["list", "work.txt"]))
+        );
+        assert_eq!(
+            Err(CliError::MissingArgument("name".to_string())),
+            parse_args(&args(&["code", "--json"]))
+        );
+        assert_eq!(
+            Err(CliError::MissingValue("--file".to_string())),
+            parse_args(&args(&["list", "--file"]))
+        );
+        assert_eq!(
+            Err(CliError::InvalidValue(
+                "--action".to_string(),
+                "paste".to_string()
+            )),
+            parse_args(&args(&["menu", "--action", "paste"]))
+        );
+        assert_eq!(
+            Err(CliError::InvalidValue(
+                "--lock-timeout".to_string(),
+                "soon".to_string()
+            )),
+            parse_args(&args(&["agent", "--lock-timeout", "soon"]))
+        );
+        assert_eq!(
+            Err(CliError::MissingArgument("command".to_string())),
+            parse_args(&args(&["exec", "Acme Inc."]))
+        );
+        assert_eq!(
+            Err(CliError::InvalidValue(
+                "--min-remaining".to_string(),
+                "30".to_string()
+            )),
+            parse_args(&args(&[
+                "exec",
+                "Acme Inc.",
+                "--min-remaining",
+                "30",
+                "--",
+                "echo"
+            ]))
+        );
+    }
+}
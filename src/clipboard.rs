@@ -0,0 +1,12 @@
+use std::error::Error;
+
+use arboard::{Clipboard, SetExtLinux};
+
+/// Copies the text to the clipboard, on Linux the clipboard content is owned by the process,
+/// so this blocks until another application takes over the clipboard.
+/// Used by commands that exit right after copying, the TUI keeps its own clipboard alive.
+pub fn copy_and_wait(text: &str) -> Result<(), Box<dyn Error>> {
+    Clipboard::new()?.set().wait().text(text)?;
+
+    Ok(())
+}
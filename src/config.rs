@@ -1,8 +1,18 @@
-use std::{convert::Into, error::Error, fmt, fs, io::ErrorKind, path::PathBuf};
+use std::{
+    convert::Into,
+    error::Error,
+    fmt, fs,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
 
 use regex::Regex;
+use zeroize::Zeroizing;
 
-use crate::totp::Totp;
+use crate::{document::Document, permissions, totp::Totp};
+
+/// The URI parameters that are used when parsing a TOTP entry, other parameters are ignored.
+pub const SUPPORTED_PARAMETERS: [&str; 3] = ["secret", "issuer", "tags"];
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum TotpSecretFileError {
@@ -30,7 +40,13 @@ pub fn load_totps(
     digits: u32,
     interval: u64,
 ) -> Result<Vec<Totp>, Box<dyn Error>> {
-    let secret_file_content = match fs::read_to_string(config_file_path) {
+    let secret_file_content = Zeroizing::new(read_config_file(config_file_path)?);
+
+    parse_totp_config(&secret_file_content, digits, interval)
+}
+
+pub fn read_config_file(config_file_path: &PathBuf) -> Result<String, TotpSecretFileError> {
+    match fs::read_to_string(config_file_path) {
         Ok(file_content) => Ok(file_content),
         Err(error) => match error.kind() {
             ErrorKind::NotFound => Err(TotpSecretFileError::NotFound(format!(
@@ -48,9 +64,7 @@ pub fn load_totps(
                 );
             }
         },
-    }?;
-
-    parse_totp_config(&secret_file_content, digits, interval)
+    }
 }
 
 fn parse_totp_config(
@@ -58,33 +72,62 @@ fn parse_totp_config(
     digits: u32,
     interval: u64,
 ) -> Result<Vec<Totp>, Box<dyn Error>> {
-    totp_lines
-        .split('\n')
-        // Not interested in empty lines.
-        .filter(|x| !x.is_empty())
-        // Not interested in comment lines.
-        .filter(|x| !is_comment(x))
-        .map(str::trim)
-        .map(|x| parse_uri_string_format(x, digits, interval).map_err(Into::into))
-        .collect()
+    Document::parse(totp_lines, digits, interval)
+        .into_totps()
+        .map_err(Into::into)
+}
+
+/// Returns the query parameters of an URI string as key/value pairs in the order they appear.
+/// Parameters without a value are returned with an empty value.
+pub fn query_parameters(s: &str) -> Vec<(&str, &str)> {
+    match s.split_once('?') {
+        Some((_, query)) => query
+            .split('&')
+            .filter(|x| !x.is_empty())
+            .map(|x| x.split_once('=').unwrap_or((x, "")))
+            .collect(),
+        None => vec![],
+    }
 }
 
-fn is_comment(text: &str) -> bool {
+pub fn is_comment(text: &str) -> bool {
     text.starts_with("//")
 }
 
-fn parse_uri_string_format(
+fn uri_string_format_regex() -> Regex {
+    Regex::new(r"(?i)^otpauth://totp/(.*):(.*?)secret=(.*)&issuer=.*$")
+        .expect("Could not parse regex.")
+}
+
+pub fn parse_uri_string_format(
     s: &str,
     digits: u32,
     interval: u64,
 ) -> Result<Totp, TotpSecretFileError> {
-    let re = Regex::new(r"(?i)^otpauth://totp/(.*):.*?secret=(.*)&issuer=.*$")
-        .expect("Could not parse regex.");
-
-    if let Some(totp) = re
-        .captures(s)
-        .map(|captures| Totp::new(&captures[1], &captures[2], digits, interval))
-    {
+    if let Some(totp) = uri_string_format_regex().captures(s).map(|captures| {
+        // The account is the part of the label after the name, the query starts after the '?'.
+        let account = captures[2].split('?').next().unwrap_or_default();
+        // Other parameters can come between the secret and the issuer.
+        let secret = captures[3].split('&').next().unwrap_or_default();
+        let parameter = |name: &str| {
+            query_parameters(s)
+                .into_iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+                .unwrap_or_default()
+        };
+        // Tags are separated by commas, for example 'tags=work,vpn'.
+        let tags = parameter("tags")
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>();
+
+        Totp::new(&captures[1], secret, digits, interval)
+            .with_account(account)
+            .with_issuer(parameter("issuer"))
+            .with_tags(&tags)
+    }) {
         Ok(totp)
     } else {
         Err(TotpSecretFileError::InvalidFormat(format!(
@@ -93,35 +136,202 @@ fn parse_uri_string_format(
     }
 }
 
+// Characters that separate the parts of the URI string, a name containing them would be read back differently.
+const RESERVED_NAME_CHARACTERS: [char; 5] = [':', '?', '&', '=', '#'];
+
+/// Checks that the name can be written to an URI string and read back as the same name.
+pub fn validate_name(name: &str) -> Result<(), TotpSecretFileError> {
+    if name.trim().is_empty() {
+        Err(TotpSecretFileError::InvalidFormat(
+            "The name can not be empty.".to_string(),
+        ))
+    } else if let Some(character) = name
+        .chars()
+        .find(|x| RESERVED_NAME_CHARACTERS.contains(x) || x.is_control())
+    {
+        Err(TotpSecretFileError::InvalidFormat(format!(
+            "The name can not contain {character:?}."
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Creates an URI string for a TOTP entry from its name and secret.
+pub fn create_uri_string_format(name: &str, secret: &str) -> Result<String, TotpSecretFileError> {
+    validate_name(name)?;
+
+    Ok(format!(
+        "otpauth://totp/{name}:{name}?secret={secret}&issuer={name}"
+    ))
+}
+
+/// Replaces the name in the URI string, the rest of the URI string is kept as it is.
+pub fn rename_uri_string_format(s: &str, name: &str) -> Result<String, TotpSecretFileError> {
+    validate_name(name)?;
+
+    match uri_string_format_regex()
+        .captures(s)
+        .and_then(|captures| captures.get(1))
+    {
+        Some(name_match) => Ok(format!(
+            "{}{name}{}",
+            &s[..name_match.start()],
+            &s[name_match.end()..]
+        )),
+        None => Err(TotpSecretFileError::InvalidFormat(format!(
+            "Could not parse the line, invalid format: '{s}', please refer to the documentation."
+        ))),
+    }
+}
+
+/// Adds the URI string as a new entry at the end of the TOTP secrets file.
+/// Returns the TOTPs of the updated file.
+pub fn add_entry(
+    config_file_path: &PathBuf,
+    uri: &str,
+    digits: u32,
+    interval: u64,
+) -> Result<Vec<Totp>, Box<dyn Error>> {
+    update_config_file(config_file_path, digits, interval, |document| {
+        document.append_entry(uri)
+    })
+}
+
+/// Renames the entry at the index, the index is the position of the entry in the loaded TOTPs.
+/// Returns the TOTPs of the updated file.
+pub fn rename_entry(
+    config_file_path: &PathBuf,
+    index: usize,
+    name: &str,
+    digits: u32,
+    interval: u64,
+) -> Result<Vec<Totp>, Box<dyn Error>> {
+    update_config_file(config_file_path, digits, interval, |document| {
+        document.rename_entry(index, name)
+    })
+}
+
+/// Deletes the entry at the index, the index is the position of the entry in the loaded TOTPs.
+/// Returns the TOTPs of the updated file.
+pub fn delete_entry(
+    config_file_path: &PathBuf,
+    index: usize,
+    digits: u32,
+    interval: u64,
+) -> Result<Vec<Totp>, Box<dyn Error>> {
+    update_config_file(config_file_path, digits, interval, |document| {
+        document.remove_entry(index)
+    })
+}
+
+// Reads the file, updates the document and writes it back if the updated document is valid.
+fn update_config_file(
+    config_file_path: &PathBuf,
+    digits: u32,
+    interval: u64,
+    update: impl FnOnce(&mut Document) -> Result<(), TotpSecretFileError>,
+) -> Result<Vec<Totp>, Box<dyn Error>> {
+    let content = Zeroizing::new(read_config_file(config_file_path)?);
+    let mut document = Document::parse(&content, digits, interval);
+    update(&mut document)?;
+    let content = Zeroizing::new(document.to_string());
+    let totps = document.into_totps()?;
+
+    write_config_file(config_file_path, &content)?;
+
+    Ok(totps)
+}
+
+// The content is written to a temporary file that is renamed afterwards,
+// that way the secrets file is never left half written.
+// The temporary file is created new and only accessible by the user before anything is written to it,
+// so other users can never read the secrets from it.
+fn write_config_file(config_file_path: &PathBuf, content: &str) -> Result<(), Box<dyn Error>> {
+    let mut temporary_file_name = config_file_path
+        .file_name()
+        .expect("The TOTP secret file-path has no file name.")
+        .to_os_string();
+    temporary_file_name.push(".tmp");
+    let temporary_file_path = config_file_path.with_file_name(temporary_file_name);
+
+    let mut temporary_file = permissions::create_private_file(&temporary_file_path)?;
+    temporary_file.write_all(content.as_bytes())?;
+    temporary_file.sync_all()?;
+    fs::set_permissions(
+        &temporary_file_path,
+        fs::metadata(config_file_path)?.permissions(),
+    )?;
+    fs::rename(&temporary_file_path, config_file_path)?;
+
+    Ok(())
+}
+
+// The parsing tests fail with `assert!(false)` when the content can not be parsed.
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants, clippy::semicolon_if_nothing_returned)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn can_get_query_parameters() {
+        let assertions = [
+            (
+                "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp",
+                vec![("secret", "GZMWV5JLOMNI2XJL"), ("issuer", "AcmeCorp")],
+            ),
+            (
+                "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&digits=8&issuer=AcmeCorp&image",
+                vec![
+                    ("secret", "GZMWV5JLOMNI2XJL"),
+                    ("digits", "8"),
+                    ("issuer", "AcmeCorp"),
+                    ("image", ""),
+                ],
+            ),
+            ("Otpauth://totp/Acme Inc.:me@my-domain.com", vec![]),
+        ];
+
+        for (input, expected) in assertions {
+            assert_eq!(expected, query_parameters(input));
+        }
+    }
+
     #[test]
     fn can_parse_uri_string_format() {
         let interval = 30;
         let digits = 6;
 
         let assertions = [
-            (Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", digits, interval),
+            (Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("AcmeCorp"),
              "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp"),
-            (Totp::new("Widget Co", "JXQWZ4TVRNUP5YKM", digits, interval),
+            (Totp::new("Widget Co", "JXQWZ4TVRNUP5YKM", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("WidgetCo"),
              "Otpauth://totp/Widget Co:me@my-domain.com?secret=JXQWZ4TVRNUP5YKM&issuer=WidgetCo"),
-            (Totp::new("Foobar Inc.", "KBYXAdigits_countUSSPQ7ZLNN", digits, interval),
+            (Totp::new("Foobar Inc.", "KBYXAdigits_countUSSPQ7ZLNN", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("FoobarInc"),
              "Otpauth://totp/Foobar Inc.:me@my-domain.com?secret=KBYXAdigits_countUSSPQ7ZLNN&issuer=FoobarInc"),
-            (Totp::new("Globex Corp.", "LCZYB7VTTSR8AMOO", digits, interval),
+            (Totp::new("Globex Corp.", "LCZYB7VTTSR8AMOO", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("GlobexCorp"),
              "Otpauth://totp/Globex Corp.:me@my-domain.com?secret=LCZYB7VTTSR8AMOO&issuer=GlobexCorp"),
-            (Totp::new("Big Corp.", "MDAZC8WUUTS9BNPP", digits, interval),
+            (Totp::new("Big Corp.", "MDAZC8WUUTS9BNPP", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("BigCorp"),
              "Otpauth://totp/Big Corp.:me@my-domain.com?secret=MDAZC8WUUTS9BNPP&issuer=BigCorp"),
-            (Totp::new("Small Firm.", "NEBAD9XVVUT0COQQ", digits, interval),
+            (Totp::new("Small Firm.", "NEBAD9XVVUT0COQQ", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("SmallFirm"),
              "Otpauth://totp/Small Firm.:me@my-domain.com?secret=NEBAD9XVVUT0COQQ&issuer=SmallFirm"),
-            (Totp::new("Mega Corp.", "OFCAE0YWWVU1DPRR", digits, interval),
+            (Totp::new("Mega Corp.", "OFCAE0YWWVU1DPRR", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("MegaCorp"),
              "Otpauth://totp/Mega Corp.:me@my-domain.com?secret=OFCAE0YWWVU1DPRR&issuer=MegaCorp"),
-            (Totp::new("Tech Co.", "PGDBF1ZXWXU2EQSS", digits, interval),
+            (Totp::new("Tech Co.", "PGDBF1ZXWXU2EQSS", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("TechCo"),
              "Otpauth://totp/Tech Co.:me@my-domain.com?secret=PGDBF1ZXWXU2EQSS&issuer=TechCo"),
-            (Totp::new("Startup Inc.", "QHECK2AYXYU3FRTT", digits, interval),
+            (Totp::new("Startup Inc.", "QHECK2AYXYU3FRTT", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("StartupInc"),
              "Otpauth://totp/Startup Inc.:me@my-domain.com?secret=QHECK2AYXYU3FRTT&issuer=StartupInc"),
-            (Totp::new("Consulting Firm", "RIFDL3BZYZU4GSUU", digits, interval),
+            (Totp::new("Consulting Firm", "RIFDL3BZYZU4GSUU", digits, interval)
+                .with_account("me@my-domain.com").with_issuer("ConsultingFirm"),
              "Otpauth://totp/Consulting Firm:me@my-domain.com?secret=RIFDL3BZYZU4GSUU&issuer=ConsultingFirm")];
 
         for (expected, input) in assertions {
@@ -306,4 +516,172 @@ Otpauth://totp/Globex Corp.:me@my-domain.com?secret=JBSWY3DPFQQFO33SNRSCC===&iss
             assert!(is_comment(value) == expected);
         }
     }
+
+    #[test]
+    fn can_rename_uri_string_format() {
+        assert_eq!(
+            Ok("Otpauth://totp/Acme Corp.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp".to_string()),
+            rename_uri_string_format(
+                "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp",
+                "Acme Corp."
+            )
+        );
+
+        assert!(matches!(
+            rename_uri_string_format("qwerty", "Acme Corp."),
+            Err(TotpSecretFileError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn names_that_would_corrupt_the_uri_string_are_refused() {
+        let uri =
+            "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp";
+
+        for name in [
+            "",
+            "  ",
+            "Acme: Inc.",
+            "Acme?",
+            "A&B",
+            "secret=ABC",
+            "#1",
+            "Acme\tInc.",
+        ] {
+            assert!(
+                matches!(
+                    create_uri_string_format(name, "GZMWV5JLOMNI2XJL"),
+                    Err(TotpSecretFileError::InvalidFormat(_))
+                ),
+                "{name:?}"
+            );
+            assert!(
+                matches!(
+                    rename_uri_string_format(uri, name),
+                    Err(TotpSecretFileError::InvalidFormat(_))
+                ),
+                "{name:?}"
+            );
+        }
+
+        assert!(create_uri_string_format("Acme Inc. (Work)", "GZMWV5JLOMNI2XJL").is_ok());
+    }
+
+    #[test]
+    fn can_parse_tags() {
+        assert_eq!(
+            Ok(Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30)
+                .with_account("me@my-domain.com")
+                .with_issuer("AcmeCorp")
+                .with_tags(&["work", "vpn"])),
+            parse_uri_string_format(
+                "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&tags=work, vpn,&issuer=AcmeCorp",
+                6,
+                30
+            )
+        );
+    }
+
+    #[test]
+    fn created_uri_string_format_can_be_parsed() {
+        let uri = create_uri_string_format("Acme Inc.", "GZMWV5JLOMNI2XJL").unwrap();
+
+        assert_eq!(
+            Ok(Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30)
+                .with_account("Acme Inc.")
+                .with_issuer("Acme Inc.")),
+            parse_uri_string_format(&uri, 6, 30)
+        );
+    }
+
+    #[test]
+    fn can_add_rename_and_delete_entries_in_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(
+            file.path(),
+            "// Comment
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+",
+        )
+        .unwrap();
+        let path = file.path().to_path_buf();
+
+        let totps = add_entry(
+            &path,
+            "Otpauth://totp/Widget Co:me@my-domain.com?secret=MFRGGZD&issuer=WidgetCo",
+            6,
+            30,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30)
+                    .with_account("me@my-domain.com")
+                    .with_issuer("AcmeCorp")
+                    .with_group(Some("Comment")),
+                Totp::new("Widget Co", "MFRGGZD", 6, 30)
+                    .with_account("me@my-domain.com")
+                    .with_issuer("WidgetCo")
+                    .with_group(Some("Comment"))
+            ],
+            totps
+        );
+
+        let totps = rename_entry(&path, 0, "Acme Corp.", 6, 30).unwrap();
+        assert_eq!(
+            vec![
+                Totp::new("Acme Corp.", "GZMWV5JLOMNI2XJL", 6, 30)
+                    .with_account("me@my-domain.com")
+                    .with_issuer("AcmeCorp")
+                    .with_group(Some("Comment")),
+                Totp::new("Widget Co", "MFRGGZD", 6, 30)
+                    .with_account("me@my-domain.com")
+                    .with_issuer("WidgetCo")
+                    .with_group(Some("Comment"))
+            ],
+            totps
+        );
+
+        let totps = delete_entry(&path, 1, 6, 30).unwrap();
+        assert_eq!(
+            vec![Totp::new("Acme Corp.", "GZMWV5JLOMNI2XJL", 6, 30)
+                .with_account("me@my-domain.com")
+                .with_issuer("AcmeCorp")
+                .with_group(Some("Comment"))],
+            totps
+        );
+
+        assert_eq!(
+            "// Comment
+Otpauth://totp/Acme Corp.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+",
+            fs::read_to_string(&path).unwrap()
+        );
+
+        // Invalid entries are never written to the file.
+        assert!(add_entry(&path, "qwerty", 6, 30).is_err());
+        assert_eq!(1, load_totps(&path, 6, 30).unwrap().len());
+    }
+
+    #[test]
+    fn temporary_file_is_private_and_does_not_follow_symlinks() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("totp.txt");
+        let temporary_file_path = directory.path().join("totp.txt.tmp");
+        let target_path = directory.path().join("target.txt");
+        fs::write(&target_path, "target").unwrap();
+        std::os::unix::fs::symlink(&target_path, &temporary_file_path).unwrap();
+
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_config_file(&path, "content").unwrap();
+
+        assert_eq!("target", fs::read_to_string(&target_path).unwrap());
+        assert_eq!("content", fs::read_to_string(&path).unwrap());
+        assert_eq!(0o600, fs::metadata(&path).unwrap().mode() & 0o777);
+        assert!(!temporary_file_path.exists());
+    }
 }
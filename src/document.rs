@@ -0,0 +1,415 @@
+use std::fmt;
+
+use zeroize::Zeroizing;
+
+use crate::config::{self, TotpSecretFileError};
+use crate::totp::Totp;
+
+/// A line of the TOTP secrets file, every line keeps its original text,
+/// so lines that are not modified are written back exactly as they were read.
+/// The text of entries contains the secret, so it is wiped from memory when dropped.
+#[derive(PartialEq, Debug)]
+pub enum Line {
+    Entry {
+        raw: Zeroizing<String>,
+        totp: Totp,
+    },
+    Comment(String),
+    Blank,
+    // Lines that could not be parsed, they are kept so no content is lost when writing.
+    Unknown {
+        raw: Zeroizing<String>,
+        error: TotpSecretFileError,
+    },
+}
+
+impl Line {
+    fn parse(raw: &str, digits: u32, interval: u64) -> Self {
+        if raw.is_empty() {
+            Line::Blank
+        } else if config::is_comment(raw) {
+            Line::Comment(raw.to_string())
+        } else {
+            match config::parse_uri_string_format(raw.trim(), digits, interval) {
+                Ok(totp) => Line::Entry {
+                    raw: Zeroizing::new(raw.to_string()),
+                    totp,
+                },
+                Err(error) => Line::Unknown {
+                    raw: Zeroizing::new(raw.to_string()),
+                    error,
+                },
+            }
+        }
+    }
+
+    pub fn raw(&self) -> &str {
+        match self {
+            Line::Entry { raw, .. } | Line::Unknown { raw, .. } => raw,
+            Line::Comment(raw) => raw,
+            Line::Blank => "",
+        }
+    }
+}
+
+/// Document model of the TOTP secrets file, used by everything that modifies the file.
+/// Entries are addressed by their index, which is their position among the entries of the file,
+/// the same position they have in the TOTPs returned by `into_totps`.
+#[derive(PartialEq, Debug)]
+pub struct Document {
+    lines: Vec<Line>,
+    digits: u32,
+    interval: u64,
+}
+
+impl Document {
+    pub fn parse(content: &str, digits: u32, interval: u64) -> Self {
+        Self {
+            // Splitting on newlines, means that a file ending with a newline
+            // has an empty last line, that way the trailing newline is kept when writing.
+            lines: content
+                .split('\n')
+                .map(|x| Line::parse(x, digits, interval))
+                .collect(),
+            digits,
+            interval,
+        }
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// The TOTPs of the document, fails on the first line that could not be parsed
+    /// or that has a secret which is not valid base32, so every returned TOTP can generate codes.
+    /// Comments act as group headers, every entry is in the group of the last comment above it.
+    /// The document is consumed, so the secrets are moved out instead of copied.
+    pub fn into_totps(self) -> Result<Vec<Totp>, TotpSecretFileError> {
+        let mut group = None;
+
+        self.lines
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, line)| match line {
+                Line::Entry { totp, .. } if totp.decoded_secret().is_none() => {
+                    Some(Err(TotpSecretFileError::InvalidFormat(format!(
+                        "The secret for '{}' on line {} is not valid base32.",
+                        totp.name,
+                        index + 1
+                    ))))
+                }
+                Line::Entry { totp, .. } => Some(Ok(totp.with_group(group.as_deref()))),
+                Line::Unknown { error, .. } => Some(Err(error)),
+                Line::Comment(raw) => {
+                    // An empty comment ends the group.
+                    group = Some(raw.trim_start_matches('/').trim().to_string())
+                        .filter(|x| !x.is_empty());
+                    None
+                }
+                Line::Blank => None,
+            })
+            .collect()
+    }
+
+    /// Appends the URI string as a new entry after the last line of the document.
+    pub fn append_entry(&mut self, uri: &str) -> Result<(), TotpSecretFileError> {
+        let totp = config::parse_uri_string_format(uri, self.digits, self.interval)?;
+        // An entry that can not generate codes would break every later load of the file.
+        if totp.decoded_secret().is_none() {
+            return Err(TotpSecretFileError::InvalidFormat(format!(
+                "The secret for '{}' is not valid base32.",
+                totp.name
+            )));
+        }
+        let line = Line::Entry {
+            raw: Zeroizing::new(uri.to_string()),
+            totp,
+        };
+
+        if let Some(Line::Blank) = self.lines.last() {
+            // Keeps the trailing newline at the end of the file.
+            self.lines.insert(self.lines.len() - 1, line);
+        } else {
+            self.lines.push(line);
+            self.lines.push(Line::Blank);
+        }
+
+        Ok(())
+    }
+
+    /// Renames the entry, the rest of the line is kept as it is.
+    pub fn rename_entry(&mut self, index: usize, name: &str) -> Result<(), TotpSecretFileError> {
+        let line_index = self.entry_line_index(index)?;
+        let raw = self.lines[line_index].raw();
+
+        // The name is replaced in the trimmed URI string, and the surrounding whitespace put back.
+        let trimmed_start = raw.len() - raw.trim_start().len();
+        let trimmed_end = raw.trim_end().len();
+        let renamed = format!(
+            "{}{}{}",
+            &raw[..trimmed_start],
+            config::rename_uri_string_format(&raw[trimmed_start..trimmed_end], name)?,
+            &raw[trimmed_end..]
+        );
+
+        self.lines[line_index] = Line::parse(&renamed, self.digits, self.interval);
+
+        Ok(())
+    }
+
+    pub fn remove_entry(&mut self, index: usize) -> Result<(), TotpSecretFileError> {
+        let line_index = self.entry_line_index(index)?;
+        self.lines.remove(line_index);
+
+        Ok(())
+    }
+
+    fn entry_line_index(&self, index: usize) -> Result<usize, TotpSecretFileError> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| matches!(line, Line::Entry { .. }))
+            .nth(index)
+            .map(|(line_index, _)| line_index)
+            .ok_or_else(|| {
+                TotpSecretFileError::InvalidFormat(format!(
+                    "Could not find entry number {} in the TOTP secrets file.",
+                    index + 1
+                ))
+            })
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.lines
+                .iter()
+                .map(Line::raw)
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const CONTENT: &str = "// Big companies
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+
+// Small companies
+  Otpauth://totp/Widget Co:me@my-domain.com?secret=MFRGGZD&issuer=WidgetCo\r
+qwerty
+";
+
+    #[test]
+    fn can_parse_lines() {
+        let document = Document::parse(CONTENT, 6, 30);
+
+        assert_eq!(
+            vec![
+                Line::Comment("// Big companies".to_string()),
+                Line::Entry {
+                    raw: Zeroizing::new("Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp".to_string()),
+                    totp: Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30)
+                        .with_account("me@my-domain.com")
+                        .with_issuer("AcmeCorp"),
+                },
+                Line::Blank,
+                Line::Comment("// Small companies".to_string()),
+                Line::Entry {
+                    raw: Zeroizing::new("  Otpauth://totp/Widget Co:me@my-domain.com?secret=MFRGGZD&issuer=WidgetCo\r".to_string()),
+                    totp: Totp::new("Widget Co", "MFRGGZD", 6, 30)
+                        .with_account("me@my-domain.com")
+                        .with_issuer("WidgetCo"),
+                },
+                Line::Unknown {
+                    raw: Zeroizing::new("qwerty".to_string()),
+                    error: config::parse_uri_string_format("qwerty", 6, 30).unwrap_err(),
+                },
+                Line::Blank,
+            ],
+            document.lines()
+        );
+
+        // The unknown line makes the document invalid.
+        assert!(matches!(
+            document.into_totps(),
+            Err(TotpSecretFileError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn secrets_that_are_not_base32_make_the_document_invalid() {
+        let document = Document::parse(
+            "// Comment
+Otpauth://totp/Acme Inc.:me@my-domain.com?secret=abcd efgh 1234&issuer=AcmeCorp",
+            6,
+            30,
+        );
+
+        assert_eq!(
+            Err(TotpSecretFileError::InvalidFormat(
+                "The secret for 'Acme Inc.' on line 2 is not valid base32.".to_string()
+            )),
+            document.into_totps()
+        );
+    }
+
+    #[test]
+    fn entries_are_in_group_of_comment_above() {
+        let document = Document::parse(
+            "Otpauth://totp/Acme Inc.:me@my-domain.com?secret=GZMWV5JLOMNI2XJL&issuer=AcmeCorp
+// Small companies
+Otpauth://totp/Widget Co:me@my-domain.com?secret=MFRGGZD&issuer=WidgetCo
+
+Otpauth://totp/Foobar Inc.:me@my-domain.com?secret=MZXW6YTBOI&issuer=FoobarInc
+//
+Otpauth://totp/Globex Corp.:me@my-domain.com?secret=JBSWY3DPFQQFO33SNRSCC===&issuer=GlobexCorp",
+            6,
+            30,
+        );
+
+        assert_eq!(
+            vec![None, Some("Small companies"), Some("Small companies"), None],
+            document
+                .into_totps()
+                .unwrap()
+                .iter()
+                .map(|x| x.group.as_deref())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn can_append_entry() {
+        let assertions = [
+            ("", "Otpauth://totp/Foobar Inc.:me@my-domain.com?secret=MZXW6YTBOI&issuer=FoobarInc\n"),
+            ("// Comment", "// Comment\nOtpauth://totp/Foobar Inc.:me@my-domain.com?secret=MZXW6YTBOI&issuer=FoobarInc\n"),
+            ("// Comment\n\n", "// Comment\n\nOtpauth://totp/Foobar Inc.:me@my-domain.com?secret=MZXW6YTBOI&issuer=FoobarInc\n"),
+        ];
+
+        for (input, expected) in assertions {
+            let mut document = Document::parse(input, 6, 30);
+            document
+                .append_entry(
+                    "Otpauth://totp/Foobar Inc.:me@my-domain.com?secret=MZXW6YTBOI&issuer=FoobarInc",
+                )
+                .unwrap();
+
+            assert_eq!(expected, document.to_string());
+        }
+    }
+
+    #[test]
+    fn invalid_entry_is_not_appended() {
+        let mut document = Document::parse(CONTENT, 6, 30);
+
+        assert!(document.append_entry("qwerty").is_err());
+        assert!(document
+            .append_entry(
+                "Otpauth://totp/Foobar Inc.:me@my-domain.com?secret=abcd efgh 1234&issuer=FoobarInc"
+            )
+            .is_err());
+        assert_eq!(CONTENT, document.to_string());
+    }
+
+    #[test]
+    fn can_rename_entry() {
+        let mut document = Document::parse(CONTENT, 6, 30);
+
+        document.rename_entry(1, "Widget Corp.").unwrap();
+
+        assert_eq!(
+            CONTENT.replace("totp/Widget Co:", "totp/Widget Corp.:"),
+            document.to_string()
+        );
+        assert!(matches!(
+            &document.lines()[4],
+            Line::Entry { totp, .. } if totp.name == "Widget Corp."
+        ));
+    }
+
+    #[test]
+    fn can_remove_entry() {
+        let mut document = Document::parse(CONTENT, 6, 30);
+
+        document.remove_entry(0).unwrap();
+
+        assert_eq!(
+            "// Big companies
+
+// Small companies
+  Otpauth://totp/Widget Co:me@my-domain.com?secret=MFRGGZD&issuer=WidgetCo\r
+qwerty
+",
+            document.to_string()
+        );
+
+        assert!(document.remove_entry(1).is_err());
+    }
+
+    // Lines that are likely to be found in a secrets file, including lines that can not be parsed.
+    fn line_strategy() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just(String::new()),
+            Just("\r".to_string()),
+            "// [ -~]{0,20}\r?",
+            "[ \t]{0,2}Otpauth://totp/[A-Za-z ]{1,10}:me@my-domain.com\\?secret=[A-Z2-7]{8,32}&issuer=[A-Za-z]{1,10}[ \t\r]{0,2}",
+            "[ -~]{0,30}",
+        ]
+    }
+
+    fn content_strategy() -> impl Strategy<Value = String> {
+        prop::collection::vec(line_strategy(), 0..20).prop_map(|lines| lines.join("\n"))
+    }
+
+    proptest! {
+        #[test]
+        fn parsing_and_writing_is_lossless(content in content_strategy()) {
+            prop_assert_eq!(&content, &Document::parse(&content, 6, 30).to_string());
+        }
+
+        #[test]
+        fn renaming_an_entry_only_changes_that_line(content in content_strategy(), index in 0..20_usize) {
+            let mut document = Document::parse(&content, 6, 30);
+
+            if let Ok(line_index) = document.entry_line_index(index) {
+                document.rename_entry(index, "Renamed").unwrap();
+                let written = document.to_string();
+
+                let original_lines = content.split('\n').collect::<Vec<_>>();
+                let written_lines = written.split('\n').collect::<Vec<_>>();
+                prop_assert_eq!(original_lines.len(), written_lines.len());
+
+                for (idx, (original, written)) in original_lines.iter().zip(&written_lines).enumerate() {
+                    if idx == line_index {
+                        prop_assert!(written.contains("totp/Renamed:"));
+                    } else {
+                        prop_assert_eq!(original, written);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn removing_an_entry_only_removes_that_line(content in content_strategy(), index in 0..20_usize) {
+            let mut document = Document::parse(&content, 6, 30);
+
+            if let Ok(line_index) = document.entry_line_index(index) {
+                document.remove_entry(index).unwrap();
+
+                let mut expected_lines = content.split('\n').collect::<Vec<_>>();
+                expected_lines.remove(line_index);
+
+                prop_assert_eq!(expected_lines.join("\n"), document.to_string());
+            }
+        }
+    }
+}
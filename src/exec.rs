@@ -0,0 +1,127 @@
+use std::{error::Error, fmt, os::unix::process::ExitStatusExt, process::Command};
+
+pub const DEFAULT_CODE_VARIABLE: &str = "TOTP_CODE";
+const CODE_PLACEHOLDER: &str = "{code}";
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ExecError {
+    CommandFailed(String),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ExecError::CommandFailed(message) => message,
+            }
+        )
+    }
+}
+
+impl Error for ExecError {}
+
+/// Replaces the `{code}` placeholders in the arguments with the code.
+pub fn substitute_code(args: &[String], code: &str) -> Vec<String> {
+    args.iter()
+        .map(|arg| arg.replace(CODE_PLACEHOLDER, code))
+        .collect()
+}
+
+/// Runs the command with the code in the environment variable and in place of the placeholders,
+/// and returns the exit status code of the command.
+/// A command killed by a signal results in 128 plus the signal number, the same as shells do.
+pub fn run(command: &[String], variable: &str, code: &str) -> Result<i32, Box<dyn Error>> {
+    let command = substitute_code(command, code);
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| ExecError::CommandFailed("The command is empty.".to_string()))?;
+
+    let status = Command::new(program)
+        .args(args)
+        .env(variable, code)
+        .status()
+        .map_err(|err| {
+            ExecError::CommandFailed(format!("Could not start command '{program}': {err}."))
+        })?;
+
+    Ok(status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn placeholders_are_replaced_with_code() {
+        assert_eq!(
+            command(&["vpn", "--otp", "123456", "--password=secret123456"]),
+            substitute_code(
+                &command(&["vpn", "--otp", "{code}", "--password=secret{code}"]),
+                "123456"
+            )
+        );
+    }
+
+    #[test]
+    fn code_is_passed_to_command() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_path = directory.path().join("output.txt");
+        let script = format!(
+            "echo \"$TOTP_CODE $1\" > '{}'",
+            output_path.to_string_lossy()
+        );
+
+        let exit_code = run(
+            &command(&["sh", "-c", &script, "sh", "{code}"]),
+            DEFAULT_CODE_VARIABLE,
+            "123456",
+        )
+        .unwrap();
+
+        assert_eq!(0, exit_code);
+        assert_eq!("123456 123456\n", fs::read_to_string(output_path).unwrap());
+    }
+
+    #[test]
+    fn exit_status_of_command_is_returned() {
+        assert_eq!(
+            3,
+            run(
+                &command(&["sh", "-c", "exit 3"]),
+                DEFAULT_CODE_VARIABLE,
+                "123456"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            128 + 9,
+            run(
+                &command(&["sh", "-c", "kill -9 $$"]),
+                DEFAULT_CODE_VARIABLE,
+                "123456"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_command_results_in_error() {
+        assert!(run(
+            &command(&["/nonexistent/command"]),
+            DEFAULT_CODE_VARIABLE,
+            "123456"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{state::State, totp::Totp};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// How often and how recently an entry was used.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub count: u64,
+    // Seconds since the UNIX epoch.
+    pub last_used: u64,
+}
+
+impl Usage {
+    fn parse(value: &str) -> Option<Self> {
+        let (count, last_used) = value.split_once(' ')?;
+
+        Some(Self {
+            count: count.parse().ok()?,
+            last_used: last_used.parse().ok()?,
+        })
+    }
+
+    /// The usage count weighted by how recently the entry was used,
+    /// the same way browsers rank their history.
+    pub fn score(&self, now: u64) -> u64 {
+        let weight = match now.saturating_sub(self.last_used) / DAY {
+            0..=3 => 100,
+            4..=13 => 70,
+            14..=30 => 50,
+            31..=89 => 30,
+            _ => 10,
+        };
+
+        self.count * weight
+    }
+}
+
+const USAGE_STATE_PREFIX: &str = "usage";
+
+pub fn usage(state: &State, totp: &Totp) -> Usage {
+    state
+        .get(&state.entry_key(USAGE_STATE_PREFIX, totp))
+        .and_then(Usage::parse)
+        .unwrap_or_default()
+}
+
+pub fn record_usage(state: &mut State, totp: &Totp, now: u64) {
+    let usage = usage(state, totp);
+
+    state.set(
+        &state.entry_key(USAGE_STATE_PREFIX, totp),
+        &format!("{} {now}", usage.count + 1),
+    );
+}
+
+/// The indices of the TOTPs ordered by frecency, entries with the same score keep their order.
+pub fn sort_by_frecency(state: &State, totps: &[Totp], now: u64) -> Vec<usize> {
+    let mut indices = (0..totps.len()).collect::<Vec<_>>();
+    indices.sort_by_key(|index| std::cmp::Reverse(usage(state, &totps[*index]).score(now)));
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MARCH_14_2020: u64 = 1_584_140_400;
+
+    fn totps() -> Vec<Totp> {
+        vec![
+            Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30),
+            Totp::new("Widget Co", "MFRGGZDF", 6, 30),
+            Totp::new("Gizmo Corporation", "JBSWY3DPFQQFO33SNRSCC===", 6, 30),
+        ]
+    }
+
+    #[test]
+    fn recent_usage_scores_higher() {
+        let usage = Usage {
+            count: 2,
+            last_used: MARCH_14_2020,
+        };
+
+        assert_eq!(200, usage.score(MARCH_14_2020 + DAY));
+        assert_eq!(140, usage.score(MARCH_14_2020 + 10 * DAY));
+        assert_eq!(20, usage.score(MARCH_14_2020 + 365 * DAY));
+    }
+
+    #[test]
+    fn entries_are_sorted_by_frecency() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut state = State::load(directory.path().join("state.txt")).unwrap();
+        let totps = totps();
+
+        record_usage(&mut state, &totps[2], MARCH_14_2020);
+        record_usage(&mut state, &totps[1], MARCH_14_2020);
+        record_usage(&mut state, &totps[2], MARCH_14_2020);
+
+        assert_eq!(
+            Usage {
+                count: 2,
+                last_used: MARCH_14_2020
+            },
+            usage(&state, &totps[2])
+        );
+        assert_eq!(
+            vec![2, 1, 0],
+            sort_by_frecency(&state, &totps, MARCH_14_2020)
+        );
+    }
+}
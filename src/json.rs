@@ -0,0 +1,121 @@
+use std::time::{Duration, SystemTime};
+
+use serde_json::{json, Value};
+
+use crate::totp::{self, Totp};
+
+/// JSON representation of a TOTP entry at the given time.
+/// The secret is only included when asked for, so the output can be passed around safely.
+pub fn totp_to_json(totp: &Totp, time: SystemTime, include_secret: bool) -> Value {
+    let mut value = json!({
+        "name": totp.name,
+        "issuer": totp.issuer,
+        "account": totp.account,
+        "group": totp.group,
+        "tags": totp.tags,
+        "code": totp.code_padded(time),
+        "next_code": totp.code_padded(time + Duration::from_secs(totp.interval)),
+        "seconds_remaining": totp::seconds_remaining(totp.interval, time),
+        "period": totp.interval,
+        "digits": totp.digits,
+    });
+
+    if include_secret {
+        value["secret"] = json!(totp.secret());
+    }
+
+    value
+}
+
+pub fn totps_to_json(totps: &[Totp], time: SystemTime, include_secrets: bool) -> Value {
+    Value::Array(
+        totps
+            .iter()
+            .map(|totp| totp_to_json(totp, time, include_secrets))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_is_converted_to_json_correctly() {
+        let march_14_2020 = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_400, 0);
+        let totp = Totp::new("Gizmo Corporation", "MFRGGZDF", 6, 30)
+            .with_issuer("GizmoCorp")
+            .with_account("me@my-domain.com")
+            .with_group(Some("Small companies"))
+            .with_tags(&["work"]);
+
+        assert_eq!(
+            json!({
+                "name": "Gizmo Corporation",
+                "issuer": "GizmoCorp",
+                "account": "me@my-domain.com",
+                "group": "Small companies",
+                "tags": ["work"],
+                "code": "711370",
+                "next_code": totp.code_padded(march_14_2020 + Duration::from_secs(30)),
+                "seconds_remaining": 30,
+                "period": 30,
+                "digits": 6,
+            }),
+            totp_to_json(&totp, march_14_2020, false)
+        );
+    }
+
+    #[test]
+    fn json_has_exactly_the_documented_fields() {
+        let time = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_400, 0);
+        let totp = Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30);
+        let fields = [
+            "name",
+            "issuer",
+            "account",
+            "group",
+            "tags",
+            "code",
+            "next_code",
+            "seconds_remaining",
+            "period",
+            "digits",
+        ];
+        let keys = |value: Value| {
+            let mut keys = value
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+
+        let mut expected = fields.map(ToString::to_string).to_vec();
+        expected.sort();
+        assert_eq!(expected, keys(totp_to_json(&totp, time, false)));
+
+        expected.push("secret".to_string());
+        expected.sort();
+        assert_eq!(expected, keys(totp_to_json(&totp, time, true)));
+    }
+
+    #[test]
+    fn secret_is_only_included_when_asked_for() {
+        let time = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_410, 0);
+        let totps = [
+            Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30),
+            Totp::new("Widget Co", "MFRGGZD", 6, 30),
+        ];
+
+        let without_secrets = totps_to_json(&totps, time, false);
+        let with_secrets = totps_to_json(&totps, time, true);
+
+        assert_eq!(Value::Null, without_secrets[0]["secret"]);
+        assert_eq!(json!("GZMWV5JLOMNI2XJL"), with_secrets[0]["secret"]);
+        assert_eq!(json!("MFRGGZD"), with_secrets[1]["secret"]);
+        assert_eq!(json!(20), with_secrets[1]["seconds_remaining"]);
+    }
+}
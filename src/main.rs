@@ -2,56 +2,206 @@
 
 use std::{
     env,
-    io::stdout,
-    path::PathBuf,
+    io::{stdout, Write},
+    path::{Path, PathBuf},
     process,
     sync::{Arc, Mutex},
-    time::SystemTime,
+    thread,
+    time::{Duration, SystemTime},
 };
 
+use agent::Agent;
 use arboard::Clipboard;
+use cli::{AgentAction, Command};
+use menu::MenuAction;
+use serde_json::Value;
+use settings::Settings;
+use state::State;
 use totp::Totp;
-use tui::{TotpLineParagraph, TotpListView};
+use tui::{Keymap, Theme, TotpLineParagraph, TotpListView};
+use watch::WatchFormat;
 
+mod agent;
+mod check;
+mod cli;
+mod clipboard;
 mod config;
+mod document;
+mod exec;
+mod frecency;
+mod json;
+mod menu;
+mod permissions;
+mod secret;
+mod settings;
+mod state;
 mod totp;
 mod tui;
+mod watch;
+
+const INTERVAL: u64 = 30;
+const DIGITS: u32 = 6;
+const SETTINGS_FILE_NAME: &str = "settings.txt";
+const STATE_FILE_NAME: &str = "state.txt";
 
 fn main() {
-    const INTERVAL: u64 = 30;
-    const DIGITS: u32 = 6;
-    const POLL_INTERVAL: u64 = 1000;
+    // Secrets in memory are not written to a core dump when the process crashes.
+    if let Err(err) = secret::disable_core_dumps() {
+        eprintln!("Warning: Could not disable core dumps: {err}");
+    }
 
     // Lets the user specify the path to the TOTP secrets file.
     // In the future we might switch to using a configuration file,
     // but for now it's fine just doing it the simple way.
     // Could also be nice to pass the secrets in using STDIN, that way
     // the user could decrypt their secrets file with their encryption algorithm of choice.
-    let args = env::args().collect::<Vec<_>>();
-    let config_file_name = if args.len() > 1 {
-        args[1].to_string()
-    } else {
-        "totp.txt".to_string()
+    let mut args = env::args().collect::<Vec<_>>();
+    let insecure = cli::take_insecure_option(&mut args);
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
     };
 
+    // Checking reports the permissions itself, and fixing them is what 'fix-perms' is for.
+    let checks_permissions = !matches!(command, Command::Check { .. } | Command::FixPerms { .. });
+    if checks_permissions && !insecure {
+        check_permissions(&config_file_path(command.config_file_name()));
+    }
+
+    match command {
+        Command::Tui {
+            config_file_name,
+            private,
+        } => run_tui(&config_file_path(&config_file_name), private),
+        Command::Check {
+            config_file_name,
+            json,
+        } => run_check(&config_file_path(&config_file_name), json),
+        Command::List {
+            config_file_name,
+            json,
+            include_secrets,
+        } => run_list(&config_file_path(&config_file_name), json, include_secrets),
+        Command::Code {
+            config_file_name,
+            name,
+            json,
+            include_secrets,
+            copy,
+        } => run_code(
+            &config_file_path(&config_file_name),
+            &name,
+            json,
+            include_secrets,
+            copy,
+        ),
+        Command::Menu {
+            config_file_name,
+            launcher,
+            action,
+        } => run_menu(
+            &config_file_path(&config_file_name),
+            launcher.as_deref(),
+            action,
+        ),
+        Command::Watch {
+            config_file_name,
+            name,
+            format,
+        } => run_watch(&config_file_path(&config_file_name), &name, format),
+        Command::Agent {
+            config_file_name,
+            socket,
+            lock_timeout,
+            foreground,
+            action,
+        } => run_agent(
+            &config_file_path(&config_file_name),
+            socket.map(PathBuf::from),
+            lock_timeout,
+            foreground,
+            action,
+        ),
+        Command::Exec {
+            config_file_name,
+            name,
+            variable,
+            min_remaining,
+            command,
+        } => run_exec(
+            &config_file_path(&config_file_name),
+            &name,
+            &variable,
+            min_remaining,
+            &command,
+        ),
+        Command::FixPerms { config_file_name } => {
+            run_fix_perms(&config_file_path(&config_file_name));
+        }
+    }
+}
+
+fn config_file_path(config_file_name: &str) -> PathBuf {
     // Getting the home directory works fine on Unix systems,
     // and this project only supports UNIX based systems.
     #[allow(deprecated)]
     let home_dir = env::home_dir().expect("Could not load user home directory.");
 
-    let clipboard = Arc::new(Mutex::new(
-        Clipboard::new().expect("Could not get access to the clipboard."),
-    ));
-
     let default_config_path = [home_dir, PathBuf::from(".config/totp-tui")]
         .iter()
         .collect();
 
-    let config_file_path: PathBuf = [default_config_path, PathBuf::from(config_file_name)]
+    [default_config_path, PathBuf::from(config_file_name)]
         .iter()
-        .collect();
+        .collect()
+}
+
+// Secrets files other users can access are refused, unless '--insecure' is given.
+fn check_permissions(config_file_path: &Path) {
+    if let Err(err) = permissions::check(config_file_path) {
+        eprintln!("Error: {err}");
+        eprintln!("Run 'totp-cli fix-perms' to fix the permissions, or pass '--insecure' to use the file anyway.");
+        process::exit(1);
+    }
+}
+
+fn load_totps(config_file_path: &PathBuf) -> Vec<Totp> {
+    match config::load_totps(config_file_path, DIGITS, INTERVAL) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn load_settings() -> Settings {
+    match Settings::load(&config_file_path(SETTINGS_FILE_NAME)) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn run_tui(config_file_path: &PathBuf, private: bool) {
+    const DEFAULT_LOCK_TIMEOUT_SECONDS: u64 = 15 * 60;
 
-    let totps: Vec<Totp> = match config::load_totps(&config_file_path, DIGITS, INTERVAL) {
+    let clipboard = Mutex::new(Clipboard::new().expect("Could not get access to the clipboard."));
+    let copy_code = Box::new(move |totp: &Totp| {
+        clipboard
+            .lock()
+            .expect("Could not lock clipboard.")
+            .set_text(totp.code_padded(SystemTime::now()))
+            .expect("Could not set text in clipboard.");
+    });
+
+    let totps = load_totps(config_file_path);
+    let state = match State::load(config_file_path.with_file_name(STATE_FILE_NAME)) {
         Ok(result) => result,
         Err(err) => {
             eprintln!("Error: {err}");
@@ -59,20 +209,56 @@ fn main() {
         }
     };
 
+    let settings = load_settings();
+    let keymap = match Keymap::from_settings(&settings) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    };
+
+    let theme = match Theme::from_settings(&settings, tui::is_monochrome_terminal()) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    };
+
+    // The TUI never locks with a timeout of 0.
+    let lock_timeout = match settings.get("tui.lock_timeout").map(str::parse) {
+        None => Some(Duration::from_secs(DEFAULT_LOCK_TIMEOUT_SECONDS)),
+        Some(Ok(0)) => None,
+        Some(Ok(seconds)) => Some(Duration::from_secs(seconds)),
+        Some(Err(err)) => {
+            eprintln!("Error: Invalid value for setting 'tui.lock_timeout': {err}.");
+            process::exit(1);
+        }
+    };
+
     let mut stdout = stdout();
 
     match tui::start(
         &mut stdout,
         vec![
-            Box::new(TotpLineParagraph::new()),
-            Box::new(TotpListView::new(
-                SystemTime::now(),
-                INTERVAL,
-                totps,
-                Arc::clone(&clipboard),
-            )),
+            Box::new(TotpLineParagraph::new().with_theme(theme)),
+            Box::new(
+                TotpListView::new(
+                    SystemTime::now(),
+                    config_file_path.clone(),
+                    DIGITS,
+                    INTERVAL,
+                    totps,
+                    copy_code,
+                    state,
+                )
+                .with_theme(theme)
+                .with_privacy(private),
+            ),
         ],
-        POLL_INTERVAL,
+        &keymap,
+        lock_timeout,
     ) {
         Ok(()) => {}
         Err(err) => {
@@ -81,3 +267,249 @@ fn main() {
         }
     };
 }
+
+fn run_check(config_file_path: &PathBuf, json: bool) {
+    let diagnostics = match check::check_file(config_file_path, DIGITS, INTERVAL) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    };
+
+    let path = config_file_path.to_string_lossy();
+    if json {
+        println!("{}", check::format_diagnostics_json(&path, &diagnostics));
+    } else {
+        println!("{}", check::format_diagnostics(&path, &diagnostics));
+    }
+
+    if check::has_errors(&diagnostics) {
+        process::exit(1);
+    }
+}
+
+// The socket of the agent, when it is set the entries are taken from the agent
+// instead of the secrets file.
+fn agent_socket_path() -> Option<PathBuf> {
+    env::var_os(agent::SOCKET_ENV).map(PathBuf::from)
+}
+
+fn agent_request(socket_path: &Path, request: &str) -> Value {
+    match agent::request(socket_path, request) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+// The agent never hands out secrets, so they are always read from the secrets file.
+fn load_entries(config_file_path: &PathBuf, include_secrets: bool) -> Value {
+    match agent_socket_path() {
+        Some(socket_path) if !include_secrets => agent_request(&socket_path, "list"),
+        _ => json::totps_to_json(
+            &load_totps(config_file_path),
+            SystemTime::now(),
+            include_secrets,
+        ),
+    }
+}
+
+fn load_entry(config_file_path: &PathBuf, name: &str, include_secrets: bool) -> Value {
+    match agent_socket_path() {
+        Some(socket_path) if !include_secrets => {
+            agent_request(&socket_path, &format!("code {name}"))
+        }
+        _ => {
+            let totps = load_totps(config_file_path);
+            json::totp_to_json(find_totp(&totps, name), SystemTime::now(), include_secrets)
+        }
+    }
+}
+
+fn entry_field<'a>(entry: &'a Value, field: &str) -> &'a str {
+    entry[field].as_str().unwrap_or_default()
+}
+
+fn run_list(config_file_path: &PathBuf, json: bool, include_secrets: bool) {
+    let entries = load_entries(config_file_path, include_secrets);
+
+    if json {
+        println!("{entries}");
+    } else {
+        for entry in entries.as_array().into_iter().flatten() {
+            println!("{}", entry_field(entry, "name"));
+        }
+    }
+}
+
+fn find_totp<'a>(totps: &'a [Totp], name: &str) -> &'a Totp {
+    if let Some(totp) = totp::find_by_name(totps, name) {
+        totp
+    } else {
+        eprintln!("Error: Could not find an entry named '{name}'.");
+        process::exit(1);
+    }
+}
+
+fn run_code(config_file_path: &PathBuf, name: &str, json: bool, include_secrets: bool, copy: bool) {
+    let entry = load_entry(config_file_path, name, include_secrets);
+
+    if copy {
+        if let Err(err) = clipboard::copy_and_wait(entry_field(&entry, "code")) {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    } else if json {
+        println!("{entry}");
+    } else {
+        println!("{}", entry_field(&entry, "code"));
+    }
+}
+
+fn run_menu(config_file_path: &PathBuf, launcher: Option<&str>, action: MenuAction) {
+    let names = load_entries(config_file_path, false)
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|entry| entry_field(entry, "name").to_string())
+        .collect::<Vec<_>>();
+    let settings = load_settings();
+
+    let result = menu::launcher_command(&settings, launcher)
+        .map_err(Into::into)
+        .and_then(|launcher_command| menu::select(&launcher_command, &names))
+        .and_then(|selected| match selected {
+            Some(name) => {
+                // The code is loaded after the selection, the one listed might have expired by now.
+                let entry = load_entry(config_file_path, name, false);
+                let code = entry_field(&entry, "code");
+                match action {
+                    MenuAction::Copy => clipboard::copy_and_wait(code),
+                    MenuAction::Type => menu::type_code(&menu::type_command(&settings), code),
+                    MenuAction::Print => {
+                        println!("{code}");
+                        Ok(())
+                    }
+                }
+            }
+            // Nothing was selected, so there is nothing to report.
+            None => process::exit(1),
+        });
+
+    if let Err(err) = result {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
+
+fn run_watch(config_file_path: &PathBuf, name: &str, format: WatchFormat) {
+    const DEFAULT_EXPIRING_SECONDS: u64 = 5;
+
+    let settings = load_settings();
+
+    let expiring_seconds = match settings.get("watch.expiring_seconds").map(str::parse) {
+        None => DEFAULT_EXPIRING_SECONDS,
+        Some(Ok(seconds)) => seconds,
+        Some(Err(err)) => {
+            eprintln!("Error: Invalid value for setting 'watch.expiring_seconds': {err}.");
+            process::exit(1);
+        }
+    };
+
+    // Writing only fails when the status bar has closed the pipe, so there is nothing to report.
+    let _ = watch::watch(
+        &mut stdout(),
+        || load_entry(config_file_path, name, false),
+        format,
+        expiring_seconds,
+    );
+}
+
+fn run_agent(
+    config_file_path: &Path,
+    socket_path: Option<PathBuf>,
+    lock_timeout: u64,
+    foreground: bool,
+    action: AgentAction,
+) {
+    let result = socket_path
+        .map_or_else(agent::socket_path, Ok)
+        .map_err(Into::into)
+        .and_then(|socket_path| match action {
+            AgentAction::Lock => agent::request(&socket_path, "lock").map(|_| ()),
+            AgentAction::Unlock => agent::request(&socket_path, "unlock").map(|_| ()),
+            AgentAction::Start => {
+                let agent = Arc::new(Agent::new(
+                    config_file_path.to_path_buf(),
+                    DIGITS,
+                    INTERVAL,
+                    Duration::from_secs(lock_timeout),
+                )?);
+                let listener = agent::bind(&socket_path)?;
+
+                // Printed the same way as ssh-agent, so the output can be evaluated by the shell.
+                println!(
+                    "{}={}; export {};",
+                    agent::SOCKET_ENV,
+                    socket_path.to_string_lossy(),
+                    agent::SOCKET_ENV
+                );
+                stdout().flush()?;
+                if !foreground {
+                    agent::daemonize()?;
+                }
+
+                agent::serve(&listener, &agent).map_err(Into::into)
+            }
+        });
+
+    if let Err(err) = result {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
+
+fn run_exec(
+    config_file_path: &PathBuf,
+    name: &str,
+    variable: &str,
+    min_remaining: u64,
+    command: &[String],
+) {
+    // Waits for the next code when the current one expires too soon to be used,
+    // the seconds remaining are rounded up, so the wait always ends after the code has changed.
+    let entry = loop {
+        let entry = load_entry(config_file_path, name, false);
+        let remaining = entry["seconds_remaining"].as_u64().unwrap_or_default();
+        if remaining >= min_remaining {
+            break entry;
+        }
+        thread::sleep(Duration::from_secs(remaining));
+    };
+
+    match exec::run(command, variable, entry_field(&entry, "code")) {
+        Ok(exit_code) => process::exit(exit_code),
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn run_fix_perms(config_file_path: &Path) {
+    match permissions::fix(config_file_path) {
+        Ok(changes) if changes.is_empty() => println!("The permissions are already secure."),
+        Ok(changes) => {
+            for change in changes {
+                println!("{change}");
+            }
+        }
+        Err(err) => {
+            eprintln!("Error: {err}");
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{ErrorKind, Write},
+    process::{Command, Stdio},
+};
+
+use crate::settings::Settings;
+
+const DEFAULT_LAUNCHER: &str = "dmenu";
+const DEFAULT_TYPE_COMMAND: &str = "xdotool type --clearmodifiers --";
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MenuAction {
+    Copy,
+    Type,
+    Print,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum MenuError {
+    UnknownLauncher(String),
+    UnknownSelection(String),
+    CommandFailed(String),
+}
+
+impl fmt::Display for MenuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MenuError::UnknownLauncher(message)
+                | MenuError::UnknownSelection(message)
+                | MenuError::CommandFailed(message) => message,
+            }
+        )
+    }
+}
+
+impl Error for MenuError {}
+
+fn default_launcher_command(launcher: &str) -> Option<&'static str> {
+    match launcher {
+        "dmenu" => Some("dmenu -i -p totp"),
+        "rofi" => Some("rofi -dmenu -i -p totp"),
+        "fuzzel" => Some("fuzzel --dmenu"),
+        "wofi" => Some("wofi --dmenu --prompt totp"),
+        _ => None,
+    }
+}
+
+// Command lines are split on whitespace, quoting is not supported.
+fn split_command_line(command_line: &str) -> Vec<String> {
+    command_line
+        .split_whitespace()
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// The command line of the launcher, the launcher is taken from the argument,
+/// then from the `menu.launcher` setting and otherwise dmenu is used.
+/// The command line of any launcher can be overridden with the `menu.launcher.<name>` setting,
+/// which also makes it possible to add launchers that are not built in.
+pub fn launcher_command(
+    settings: &Settings,
+    launcher: Option<&str>,
+) -> Result<Vec<String>, MenuError> {
+    let launcher = launcher
+        .or_else(|| settings.get("menu.launcher"))
+        .unwrap_or(DEFAULT_LAUNCHER);
+
+    settings
+        .get(&format!("menu.launcher.{launcher}"))
+        .or_else(|| default_launcher_command(launcher))
+        .map(split_command_line)
+        .ok_or_else(|| {
+            MenuError::UnknownLauncher(format!(
+                "Unknown launcher '{launcher}', it can be added with the 'menu.launcher.{launcher}' setting."
+            ))
+        })
+}
+
+/// The command used to type the code, the code is passed as the last argument.
+pub fn type_command(settings: &Settings) -> Vec<String> {
+    split_command_line(
+        settings
+            .get("menu.type_command")
+            .unwrap_or(DEFAULT_TYPE_COMMAND),
+    )
+}
+
+/// Pipes the names of the entries into the launcher and returns the selected name,
+/// `None` is returned if the selection was cancelled in the launcher.
+pub fn select<'a>(
+    launcher_command: &[String],
+    names: &'a [String],
+) -> Result<Option<&'a str>, Box<dyn Error>> {
+    let (program, args) = launcher_command
+        .split_first()
+        .ok_or_else(|| MenuError::CommandFailed("The launcher command is empty.".to_string()))?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            MenuError::CommandFailed(format!("Could not start launcher '{program}': {err}."))
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The launcher might exit without reading the names, for example when it is cancelled.
+        match stdin.write_all(names.join("\n").as_bytes()) {
+            Err(err) if err.kind() != ErrorKind::BrokenPipe => return Err(err.into()),
+            _ => {}
+        }
+    }
+
+    let output = child.wait_with_output()?;
+
+    // Launchers exit with a non-zero status code when the selection is cancelled.
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    let selection = String::from_utf8_lossy(&output.stdout);
+    let selection = selection.trim_end_matches(['\n', '\r']);
+    if selection.is_empty() {
+        return Ok(None);
+    }
+
+    names
+        .iter()
+        .find(|x| x.to_lowercase() == selection.to_lowercase())
+        .map(|x| Some(x.as_str()))
+        .ok_or_else(|| {
+            MenuError::UnknownSelection(format!("Could not find an entry named '{selection}'."))
+                .into()
+        })
+}
+
+pub fn type_code(type_command: &[String], code: &str) -> Result<(), Box<dyn Error>> {
+    let (program, args) = type_command
+        .split_first()
+        .ok_or_else(|| MenuError::CommandFailed("The type command is empty.".to_string()))?;
+
+    let status = Command::new(program)
+        .args(args)
+        .arg(code)
+        .status()
+        .map_err(|err| {
+            MenuError::CommandFailed(format!("Could not start type command '{program}': {err}."))
+        })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(
+            MenuError::CommandFailed(format!("The type command '{program}' failed: {status}."))
+                .into(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn names() -> Vec<String> {
+        vec!["Acme Inc.".to_string(), "Widget Co".to_string()]
+    }
+
+    // The fake launcher is run through 'sh', executing a file that was just written
+    // can fail with 'text file busy' when tests are run in parallel.
+    fn fake_launcher(directory: &tempfile::TempDir, script: &str) -> Vec<String> {
+        let script_path = directory.path().join("launcher.sh");
+        fs::write(&script_path, script).unwrap();
+
+        vec!["sh".to_string(), script_path.to_string_lossy().to_string()]
+    }
+
+    #[test]
+    fn selection_from_launcher_is_returned() {
+        let directory = tempfile::tempdir().unwrap();
+        let names = names();
+
+        // The launcher only selects the entry if the names has been piped into it.
+        let launcher = fake_launcher(&directory, "grep -qx 'Acme Inc.' && echo 'Widget Co'");
+
+        assert_eq!(Some("Widget Co"), select(&launcher, &names).unwrap());
+    }
+
+    #[test]
+    fn cancelled_selection_returns_none() {
+        let directory = tempfile::tempdir().unwrap();
+        let names = names();
+
+        let launcher = fake_launcher(&directory, "exit 1");
+        assert_eq!(None, select(&launcher, &names).unwrap());
+
+        let launcher = fake_launcher(&directory, "cat > /dev/null");
+        assert_eq!(None, select(&launcher, &names).unwrap());
+    }
+
+    #[test]
+    fn unknown_selection_results_in_error() {
+        let directory = tempfile::tempdir().unwrap();
+        let names = names();
+
+        let launcher = fake_launcher(&directory, "cat > /dev/null; echo 'Gizmo Corporation'");
+
+        assert!(select(&launcher, &names).is_err());
+    }
+
+    #[test]
+    fn code_is_passed_to_type_command() {
+        let directory = tempfile::tempdir().unwrap();
+        let output_path = directory.path().join("typed.txt");
+        let script_path = directory.path().join("type.sh");
+        fs::write(
+            &script_path,
+            format!("echo \"$@\" > '{}'", output_path.to_string_lossy()),
+        )
+        .unwrap();
+
+        type_code(
+            &[
+                "sh".to_string(),
+                script_path.to_string_lossy().to_string(),
+                "--".to_string(),
+            ],
+            "123456",
+        )
+        .unwrap();
+
+        assert_eq!("-- 123456\n", fs::read_to_string(output_path).unwrap());
+    }
+
+    #[test]
+    fn launcher_command_is_taken_from_argument_settings_or_default() {
+        let settings = Settings::parse(
+            "menu.launcher = wofi
+menu.launcher.rofi = rofi -dmenu -theme mine
+menu.launcher.mine = my-launcher --dmenu",
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["wofi", "--dmenu", "--prompt", "totp"],
+            launcher_command(&settings, None).unwrap()
+        );
+        assert_eq!(
+            vec!["rofi", "-dmenu", "-theme", "mine"],
+            launcher_command(&settings, Some("rofi")).unwrap()
+        );
+        assert_eq!(
+            vec!["my-launcher", "--dmenu"],
+            launcher_command(&settings, Some("mine")).unwrap()
+        );
+        assert_eq!(
+            vec!["dmenu", "-i", "-p", "totp"],
+            launcher_command(&Settings::default(), None).unwrap()
+        );
+        assert!(matches!(
+            launcher_command(&settings, Some("unknown")),
+            Err(MenuError::UnknownLauncher(_))
+        ));
+    }
+}
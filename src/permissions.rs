@@ -0,0 +1,289 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind},
+    os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+// Permission bits for the group and other users.
+const GROUP_OTHER_MODE: u32 = 0o077;
+// Write permission bits for the group and other users.
+const GROUP_OTHER_WRITE_MODE: u32 = 0o022;
+/// The mode of files only the user can access, like the secrets and state files.
+pub const PRIVATE_FILE_MODE: u32 = 0o600;
+// In a directory with the sticky bit, like /tmp, only the owner of a file can replace it.
+const STICKY_MODE: u32 = 0o1000;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum PermissionError {
+    // The file can be accessed by other users, with its mode.
+    AccessibleFile(PathBuf, u32),
+    // The directory can be written by other users, they could replace the file. With its mode.
+    WritableDirectory(PathBuf, u32),
+    NotOwned(PathBuf),
+    Io(PathBuf, String),
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PermissionError::AccessibleFile(path, mode) => write!(
+                f,
+                "The secrets file '{}' can be accessed by other users, its permissions are {:o} instead of 600.",
+                path.display(),
+                mode & 0o777
+            ),
+            PermissionError::WritableDirectory(path, mode) => write!(
+                f,
+                "The directory '{}' of the secrets file can be written by other users, its permissions are {:o}.",
+                path.display(),
+                mode & 0o777
+            ),
+            PermissionError::NotOwned(path) => {
+                write!(f, "'{}' is owned by another user.", path.display())
+            }
+            PermissionError::Io(path, message) => {
+                write!(f, "Could not check '{}': {message}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for PermissionError {}
+
+/// Whether other users have no access to a file with the mode.
+pub fn is_private(mode: u32) -> bool {
+    mode & GROUP_OTHER_MODE == 0
+}
+
+// The current user, files owned by root are trusted as well.
+fn current_user() -> u32 {
+    // SAFETY: geteuid has no arguments and always succeeds.
+    unsafe { libc::geteuid() }
+}
+
+// Whether other users can replace the files in a directory with the mode.
+fn is_replaceable_by_others(directory_mode: u32) -> bool {
+    directory_mode & GROUP_OTHER_WRITE_MODE != 0 && directory_mode & STICKY_MODE == 0
+}
+
+fn check_file(path: &Path, mode: u32, owner: u32, user: u32) -> Result<(), PermissionError> {
+    if owner != user && owner != 0 {
+        Err(PermissionError::NotOwned(path.to_path_buf()))
+    } else if !is_private(mode) {
+        Err(PermissionError::AccessibleFile(path.to_path_buf(), mode))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_directory(path: &Path, mode: u32, owner: u32, user: u32) -> Result<(), PermissionError> {
+    if owner != user && owner != 0 {
+        Err(PermissionError::NotOwned(path.to_path_buf()))
+    } else if is_replaceable_by_others(mode) {
+        Err(PermissionError::WritableDirectory(path.to_path_buf(), mode))
+    } else {
+        Ok(())
+    }
+}
+
+// `None` when the path does not exist, reading the file reports that.
+fn metadata(path: &Path) -> Result<Option<fs::Metadata>, PermissionError> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(Some(metadata)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(PermissionError::Io(path.to_path_buf(), err.to_string())),
+    }
+}
+
+// The directory of the file, an empty parent is the current directory.
+fn parent_directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Checks that the secrets file and its directory are owned by the user, that other users
+/// can not access the file and that they can not write the directory.
+/// A missing file is not an error, reading it reports that.
+pub fn check(config_file_path: &Path) -> Result<(), PermissionError> {
+    let user = current_user();
+
+    let Some(file_metadata) = metadata(config_file_path)? else {
+        return Ok(());
+    };
+    check_file(
+        config_file_path,
+        file_metadata.mode(),
+        file_metadata.uid(),
+        user,
+    )?;
+
+    let directory = parent_directory(config_file_path);
+    if let Some(directory_metadata) = metadata(directory)? {
+        check_directory(
+            directory,
+            directory_metadata.mode(),
+            directory_metadata.uid(),
+            user,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Makes the secrets file private and removes the write permissions of other users from its directory.
+/// Only a directory of the user is changed, shared directories like /tmp are left alone.
+/// Returns a description of every change, ownership can not be fixed without root.
+pub fn fix(config_file_path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut changes = vec![];
+
+    let file_mode = fs::metadata(config_file_path)?.mode();
+    if !is_private(file_mode) {
+        set_mode(config_file_path, PRIVATE_FILE_MODE)?;
+        changes.push(format!(
+            "Changed the permissions of '{}' from {:o} to {PRIVATE_FILE_MODE:o}.",
+            config_file_path.display(),
+            file_mode & 0o777
+        ));
+    }
+
+    let directory = parent_directory(config_file_path);
+    let directory_metadata = fs::metadata(directory)?;
+    let directory_mode = directory_metadata.mode();
+    if is_replaceable_by_others(directory_mode) && directory_metadata.uid() == current_user() {
+        // Only the write bits are removed, the setgid and sticky bits are kept.
+        let fixed_mode = directory_mode & 0o7777 & !GROUP_OTHER_WRITE_MODE;
+        set_mode(directory, fixed_mode)?;
+        changes.push(format!(
+            "Changed the permissions of '{}' from {:o} to {fixed_mode:o}.",
+            directory.display(),
+            directory_mode & 0o7777
+        ));
+    }
+
+    // Only the ownership is left, which needs root to change.
+    check(config_file_path)?;
+
+    Ok(changes)
+}
+
+/// Creates a new file only the user can access, so nothing written to it is ever readable by others.
+/// A file left behind at the path is replaced, removing it does not follow a symlink
+/// and creating fails when something else took its place in the meantime.
+pub fn create_private_file(path: &Path) -> io::Result<File> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(PRIVATE_FILE_MODE)
+        .open(path)
+}
+
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets_file(file_mode: u32, directory_mode: u32) -> (tempfile::TempDir, PathBuf) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("totp.txt");
+        fs::write(&path, "").unwrap();
+        set_mode(&path, file_mode).unwrap();
+        set_mode(directory.path(), directory_mode).unwrap();
+
+        (directory, path)
+    }
+
+    #[test]
+    fn only_private_files_in_directories_of_the_user_are_accepted() {
+        let assertions = [
+            (0o600, 0o700, true),
+            (0o400, 0o755, true),
+            (0o700, 0o750, true),
+            (0o640, 0o700, false),
+            (0o604, 0o700, false),
+            (0o666, 0o700, false),
+            (0o600, 0o775, false),
+            (0o600, 0o777, false),
+        ];
+
+        for (file_mode, directory_mode, expected) in assertions {
+            let (_directory, path) = secrets_file(file_mode, directory_mode);
+            assert_eq!(
+                expected,
+                check(&path).is_ok(),
+                "{file_mode:o} in {directory_mode:o}"
+            );
+        }
+
+        assert_eq!(Ok(()), check(Path::new("/does/not/exist/totp.txt")));
+    }
+
+    #[test]
+    fn files_of_other_users_are_refused() {
+        let path = Path::new("totp.txt");
+
+        assert_eq!(
+            Err(PermissionError::NotOwned(path.to_path_buf())),
+            check_file(path, 0o100_600, 1001, 1000)
+        );
+        assert_eq!(Ok(()), check_file(path, 0o100_600, 1000, 1000));
+        // Files of root are trusted.
+        assert_eq!(Ok(()), check_file(path, 0o100_600, 0, 1000));
+        assert_eq!(
+            Err(PermissionError::NotOwned(path.to_path_buf())),
+            check_directory(path, 0o040_700, 1001, 1000)
+        );
+    }
+
+    #[test]
+    fn fixing_makes_the_file_private() {
+        let (directory, path) = secrets_file(0o644, 0o777);
+
+        let changes = fix(&path).unwrap();
+
+        assert_eq!(2, changes.len());
+        assert_eq!(0o600, fs::metadata(&path).unwrap().mode() & 0o777);
+        assert_eq!(
+            0o755,
+            fs::metadata(directory.path()).unwrap().mode() & 0o777
+        );
+        assert_eq!(Ok(()), check(&path));
+        assert!(fix(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fixing_keeps_the_setgid_bit_of_the_directory() {
+        let (directory, path) = secrets_file(0o600, 0o2777);
+
+        fix(&path).unwrap();
+
+        assert_eq!(
+            0o2755,
+            fs::metadata(directory.path()).unwrap().mode() & 0o7777
+        );
+    }
+
+    #[test]
+    fn shared_sticky_directories_are_accepted_and_left_alone() {
+        let (directory, path) = secrets_file(0o600, 0o1777);
+
+        assert_eq!(Ok(()), check(&path));
+        assert!(fix(&path).unwrap().is_empty());
+        assert_eq!(
+            0o1777,
+            fs::metadata(directory.path()).unwrap().mode() & 0o7777
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use std::{fmt, io};
+
+use base32::Alphabet;
+use zeroize::Zeroize;
+
+/// The secret of a TOTP, the base32 text from the secrets file and the key decoded from it.
+/// It can not be cloned, so every secret is in memory once. The memory is wiped when the secret
+/// is dropped, and locked where possible so it is not swapped to disk.
+pub struct Secret {
+    text: Box<[u8]>,
+    // `None` when the text is not valid base32.
+    key: Option<Box<[u8]>>,
+}
+
+impl Secret {
+    pub fn new(text: &str) -> Self {
+        let mut upper_case_text = text.to_ascii_uppercase();
+        let key = base32::decode(Alphabet::RFC4648 { padding: false }, &upper_case_text).map(
+            |mut decoded| {
+                // Copied into a box of the exact size, converting might leave the vector's memory behind.
+                let key = Box::<[u8]>::from(decoded.as_slice());
+                decoded.zeroize();
+                key
+            },
+        );
+        upper_case_text.zeroize();
+
+        let secret = Self {
+            text: Box::from(text.as_bytes()),
+            key,
+        };
+        lock_memory(&secret.text);
+        if let Some(key) = &secret.key {
+            lock_memory(key);
+        }
+
+        secret
+    }
+
+    pub fn text(&self) -> &str {
+        // The text is created from a `&str`, so it is always valid UTF-8.
+        std::str::from_utf8(&self.text).unwrap_or_default()
+    }
+
+    pub fn key(&self) -> Option<&[u8]> {
+        self.key.as_deref()
+    }
+}
+
+impl Drop for Secret {
+    // The pages are not unlocked, they might hold other secrets.
+    fn drop(&mut self) {
+        self.text.zeroize();
+        if let Some(key) = &mut self.key {
+            key.zeroize();
+        }
+    }
+}
+
+impl PartialEq for Secret {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for Secret {}
+
+// The secret is never printed, not even in debug output.
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+// Locking fails when the limit of locked memory is reached, the secret can still be used then.
+fn lock_memory(bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    // SAFETY: the pointer and length are of a live allocation, mlock does not access the memory.
+    unsafe {
+        libc::mlock(bytes.as_ptr().cast(), bytes.len());
+    }
+}
+
+/// Disables core dumps, so the secrets in memory are not written to disk when the process crashes.
+/// On Linux this also keeps other processes of the user from reading the memory of the process.
+pub fn disable_core_dumps() -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    // SAFETY: the limit is a valid rlimit that outlives the call.
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &raw const limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[cfg(target_os = "linux")]
+    // SAFETY: PR_SET_DUMPABLE only takes integer arguments.
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_is_decoded_from_the_text() {
+        let secret = Secret::new("mfrggzdf");
+
+        assert_eq!("mfrggzdf", secret.text());
+        assert_eq!(Some(&b"abcde"[..]), secret.key());
+        assert_eq!(None, Secret::new("not base32!").key());
+    }
+
+    #[test]
+    fn secret_is_not_in_debug_output() {
+        let secret = Secret::new("MFRGGZDF");
+
+        assert_eq!("Secret(..)", format!("{secret:?}"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::{error::Error, fmt, fs, io::ErrorKind, path::PathBuf};
+
+use crate::config;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum SettingsError {
+    InvalidFormat(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SettingsError::InvalidFormat(message) => message,
+            }
+        )
+    }
+}
+
+impl Error for SettingsError {}
+
+/// Settings are read from a file next to the TOTP secrets file, each line is a `key = value` pair,
+/// empty lines and lines starting with '//' are ignored the same way as in the secrets file.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Settings {
+    values: Vec<(String, String)>,
+}
+
+impl Settings {
+    /// Loads the settings file, a missing settings file results in the default settings.
+    pub fn load(settings_file_path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(settings_file_path) {
+            Ok(content) => Ok(Self::parse(&content)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, SettingsError> {
+        let values = content
+            .split('\n')
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, x)| !x.is_empty() && !config::is_comment(x))
+            .map(|(index, x)| match x.split_once('=') {
+                Some((key, value)) => Ok((key.trim().to_string(), value.trim().to_string())),
+                None => Err(SettingsError::InvalidFormat(format!(
+                    "Could not parse line {} of the settings file, expected 'key = value': '{x}'.",
+                    index + 1
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { values })
+    }
+
+    /// The value of the setting, if a setting is given multiple times the last one is used.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(x, _)| x == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn into_values(self) -> Vec<(String, String)> {
+        self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_settings() {
+        let content = "// Launchers
+menu.launcher = rofi
+menu.launcher.rofi = rofi -dmenu -i -p totp
+
+menu.launcher = wofi
+  menu.type_command=wtype --
+";
+
+        let settings = Settings::parse(content).unwrap();
+
+        assert_eq!(Some("wofi"), settings.get("menu.launcher"));
+        assert_eq!(
+            Some("rofi -dmenu -i -p totp"),
+            settings.get("menu.launcher.rofi")
+        );
+        assert_eq!(Some("wtype --"), settings.get("menu.type_command"));
+        assert_eq!(None, settings.get("menu.launcher.dmenu"));
+    }
+
+    #[test]
+    fn invalid_line_results_in_invalid_format_error() {
+        assert!(matches!(
+            Settings::parse("menu.launcher = rofi\nmenu.launcher"),
+            Err(SettingsError::InvalidFormat(message)) if message.contains("line 2")
+        ));
+    }
+
+    #[test]
+    fn missing_settings_file_results_in_default_settings() {
+        let directory = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            Settings::default(),
+            Settings::load(&directory.path().join("settings.txt")).unwrap()
+        );
+    }
+}
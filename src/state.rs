@@ -0,0 +1,193 @@
+use std::{
+    error::Error,
+    fmt::Write as _,
+    fs::{self, File},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use zeroize::Zeroizing;
+
+use crate::{permissions::create_private_file, settings::Settings, totp::Totp};
+
+/// State kept between runs, like usage statistics and the sort mode of the TUI.
+/// It uses the same `key = value` format as the settings file, but it is written by the application.
+/// Like the secrets file, it is only accessible by the user.
+pub struct State {
+    path: PathBuf,
+    values: Vec<(String, String)>,
+    // The random key of this installation the entry keys are derived with, kept beside the state file.
+    key: Zeroizing<Vec<u8>>,
+}
+
+// The number of random bytes of the key the entry keys are derived with.
+const KEY_LENGTH: usize = 32;
+
+impl State {
+    /// Loads the state file, a missing state file results in an empty state.
+    /// The key the entry keys are derived with is created the first time.
+    pub fn load(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let values = match fs::read_to_string(&path) {
+            Ok(content) => Settings::parse(&content)?.into_values(),
+            Err(error) if error.kind() == ErrorKind::NotFound => vec![],
+            Err(error) => return Err(error.into()),
+        };
+        let key = load_or_create_key(&path.with_extension("key"))?;
+
+        Ok(Self { path, values, key })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(x, _)| x == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.values.iter_mut().find(|(x, _)| x == key) {
+            Some((_, old_value)) => *old_value = value.to_string(),
+            None => self.values.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.retain(|(x, _)| x != key);
+    }
+
+    /// Writes the state to a temporary file first, so a failed write does not lose the old state.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let content = self
+            .values
+            .iter()
+            .map(|(key, value)| format!("{key} = {value}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let temporary_file_path = self.path.with_extension("tmp");
+        create_private_file(&temporary_file_path)?.write_all(content.as_bytes())?;
+        fs::rename(&temporary_file_path, &self.path)?;
+
+        Ok(())
+    }
+
+    /// The key of a value stored for an entry, like its usage or pin.
+    /// Entries are keyed by an HMAC of the secret with the key of this installation,
+    /// so the state file does not reveal the secret, not even when it is short enough to guess,
+    /// and the values are kept when the entry is renamed.
+    pub fn entry_key(&self, prefix: &str, totp: &Totp) -> String {
+        let secret = totp
+            .decoded_secret()
+            .unwrap_or_else(|| totp.secret().as_bytes());
+
+        Hmac::<Sha1>::new_from_slice(&self.key)
+            .expect("HMAC can take a key of any size.")
+            .chain_update(secret)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .fold(format!("{prefix}."), |mut key, byte| {
+                let _ = write!(key, "{byte:02x}");
+                key
+            })
+    }
+}
+
+fn load_or_create_key(path: &Path) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    match fs::read(path) {
+        Ok(key) if key.len() == KEY_LENGTH => return Ok(Zeroizing::new(key)),
+        Ok(_) => {
+            return Err(format!(
+                "The state key '{}' is invalid, remove it to create a new one.",
+                path.display()
+            )
+            .into())
+        }
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+        Err(_) => {}
+    }
+
+    let mut key = Zeroizing::new(vec![0; KEY_LENGTH]);
+    File::open("/dev/urandom")?.read_exact(&mut key)?;
+    create_private_file(path)?.write_all(&key)?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_is_saved_and_loaded() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("state.txt");
+
+        let mut state = State::load(path.clone()).unwrap();
+        assert_eq!(None, state.get("sort"));
+
+        state.set("sort", "frecency");
+        state.set("usage.abc", "1 1584140400");
+        state.set("usage.abc", "2 1584140430");
+        state.set("usage.def", "1 1584140400");
+        state.remove("usage.def");
+        state.save().unwrap();
+
+        let state = State::load(path).unwrap();
+        assert_eq!(Some("frecency"), state.get("sort"));
+        assert_eq!(Some("2 1584140430"), state.get("usage.abc"));
+        assert_eq!(None, state.get("usage.def"));
+    }
+
+    #[test]
+    fn secret_is_not_stored_in_entry_key() {
+        let directory = tempfile::tempdir().unwrap();
+        let state = State::load(directory.path().join("state.txt")).unwrap();
+        let totp = Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30);
+        let renamed = Totp::new("Renamed", "GZMWV5JLOMNI2XJL", 6, 30);
+
+        assert!(state.entry_key("pin", &totp).starts_with("pin."));
+        assert!(!state.entry_key("pin", &totp).contains(totp.secret()));
+        assert_eq!(
+            state.entry_key("pin", &totp),
+            state.entry_key("pin", &renamed)
+        );
+    }
+
+    #[test]
+    fn entry_keys_depend_on_the_key_of_the_installation() {
+        let directory = tempfile::tempdir().unwrap();
+        let other_directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("state.txt");
+        let totp = Totp::new("Widget Co", "MFRGGZD", 6, 30);
+
+        let state = State::load(path.clone()).unwrap();
+        let other_state = State::load(other_directory.path().join("state.txt")).unwrap();
+
+        // The key is kept, so the values are found again the next time.
+        assert_eq!(
+            state.entry_key("pin", &totp),
+            State::load(path).unwrap().entry_key("pin", &totp)
+        );
+        assert_ne!(
+            state.entry_key("pin", &totp),
+            other_state.entry_key("pin", &totp)
+        );
+    }
+
+    #[test]
+    fn state_and_key_are_only_accessible_by_the_user() {
+        use std::os::unix::fs::MetadataExt;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("state.txt");
+
+        State::load(path.clone()).unwrap().save().unwrap();
+
+        for path in [path.clone(), path.with_extension("key")] {
+            assert_eq!(0o600, fs::metadata(path).unwrap().mode() & 0o777);
+        }
+    }
+}
@@ -1,27 +1,69 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use base32::Alphabet;
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+use crate::secret::Secret;
+
+/// A TOTP entry of the secrets file, it can not be cloned so its secret is not copied around.
+#[derive(Debug, PartialEq, Eq)]
 pub struct Totp {
     pub name: String,
-    secret: String,
+    secret: Secret,
     pub digits: u32,
     pub interval: u64,
+    pub issuer: String,
+    pub account: String,
+    // The comment header the entry sits under in the secrets file.
+    pub group: Option<String>,
+    pub tags: Vec<String>,
 }
 
 impl Totp {
     pub fn new(name: &str, secret: &str, digits: u32, interval: u64) -> Self {
         Self {
             name: name.to_string(),
-            secret: secret.to_string(),
+            secret: Secret::new(secret),
             digits,
             interval,
+            issuer: String::new(),
+            account: String::new(),
+            group: None,
+            tags: vec![],
         }
     }
 
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = issuer.to_string();
+        self
+    }
+
+    pub fn with_account(mut self, account: &str) -> Self {
+        self.account = account.to_string();
+        self
+    }
+
+    pub fn with_group(mut self, group: Option<&str>) -> Self {
+        self.group = group.map(ToString::to_string);
+        self
+    }
+
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    /// Whether the entry has a tag starting with the text, tags are matched case-insensitively.
+    pub fn has_tag_starting_with(&self, text: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|tag| tag.to_lowercase().starts_with(&text.to_lowercase()))
+    }
+
+    pub fn secret(&self) -> &str {
+        self.secret.text()
+    }
+
     pub fn code_padded(&self, time: SystemTime) -> String {
         format!(
             "{:0digits_width$}",
@@ -30,20 +72,28 @@ impl Totp {
         )
     }
 
-    fn code(&self, time: SystemTime) -> u32 {
-        let counter = time
-            .duration_since(SystemTime::UNIX_EPOCH)
+    /// The secret decoded from base32, `None` if the secret is not valid base32.
+    pub fn decoded_secret(&self) -> Option<&[u8]> {
+        self.secret.key()
+    }
+
+    /// The number of intervals since the UNIX epoch, the code changes when the counter does.
+    pub fn counter(&self, time: SystemTime) -> u64 {
+        time.duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs()
-            / self.interval;
+            / self.interval
+    }
 
-        let decoded_secret = base32::decode(
-            Alphabet::RFC4648 { padding: false },
-            &self.secret.to_ascii_uppercase(),
-        )
-        .unwrap();
+    fn code(&self, time: SystemTime) -> u32 {
+        let counter = self.counter(time);
 
-        let digest = Hmac::<Sha1>::new_from_slice(&decoded_secret)
+        // Loading the secrets file refuses entries with secrets that are not valid base32.
+        let decoded_secret = self
+            .decoded_secret()
+            .expect("The secret is not valid base32.");
+
+        let digest = Hmac::<Sha1>::new_from_slice(decoded_secret)
             .unwrap()
             .chain_update(counter.to_be_bytes())
             .finalize()
@@ -61,6 +111,13 @@ impl Totp {
     }
 }
 
+/// Finds the TOTP with the name, the name is matched case-insensitively.
+pub fn find_by_name<'a>(totps: &'a [Totp], name: &str) -> Option<&'a Totp> {
+    totps
+        .iter()
+        .find(|x| x.name.to_lowercase() == name.to_lowercase())
+}
+
 pub fn duration_used(interval: u64, time: SystemTime) -> u64 {
     time.duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
@@ -68,6 +125,20 @@ pub fn duration_used(interval: u64, time: SystemTime) -> u64 {
         % interval
 }
 
+pub fn seconds_remaining(interval: u64, time: SystemTime) -> u64 {
+    interval - duration_used(interval, time)
+}
+
+/// The time the next code of the interval starts.
+pub fn next_rollover(interval: u64, time: SystemTime) -> SystemTime {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    SystemTime::UNIX_EPOCH + Duration::from_secs((seconds / interval + 1) * interval)
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
@@ -98,4 +169,34 @@ mod tests {
             assert_eq!(expected, totp.code(march_14_2020));
         }
     }
+
+    #[test]
+    fn next_rollover_is_the_start_of_the_next_interval() {
+        let march_14_2020 = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_400, 0);
+
+        assert_eq!(
+            march_14_2020 + Duration::from_secs(30),
+            next_rollover(30, march_14_2020)
+        );
+        assert_eq!(
+            march_14_2020 + Duration::from_secs(30),
+            next_rollover(30, march_14_2020 + Duration::from_millis(29_999))
+        );
+        assert_eq!(
+            march_14_2020 + Duration::from_mins(1),
+            next_rollover(60, march_14_2020 + Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn can_find_by_name() {
+        let totps = [
+            Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30),
+            Totp::new("Widget Co", "MFRGGZD", 6, 30),
+        ];
+
+        assert_eq!(Some(&totps[1]), find_by_name(&totps, "Widget Co"));
+        assert_eq!(Some(&totps[1]), find_by_name(&totps, "widget co"));
+        assert_eq!(None, find_by_name(&totps, "Widget"));
+    }
 }
@@ -0,0 +1,275 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor, queue,
+    style::{self, ContentStyle},
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::text;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cell {
+    // The grapheme shown in the cell, empty for the second cell of a wide grapheme.
+    pub symbol: String,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: " ".to_string(),
+            style: ContentStyle::new(),
+        }
+    }
+}
+
+/// A screen of cells the elements are drawn on, before it is written to the terminal.
+/// Text is written at the cursor the same way as printing to the terminal,
+/// text outside of the screen is cut off.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Buffer {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    // The column and row text is written at.
+    cursor: (u16, u16),
+    // The column the cursor moves to on the next line.
+    line_start: u16,
+}
+
+impl Buffer {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); usize::from(width) * usize::from(height)],
+            cursor: (0, 0),
+            line_start: 0,
+        }
+    }
+
+    fn index(&self, column: u16, row: u16) -> Option<usize> {
+        (column < self.width && row < self.height)
+            .then(|| usize::from(row) * usize::from(self.width) + usize::from(column))
+    }
+
+    pub fn cell(&self, column: u16, row: u16) -> Option<&Cell> {
+        self.index(column, row).map(|index| &self.cells[index])
+    }
+
+    // Overwriting half of a wide grapheme clears the other half, so the row keeps its width.
+    fn set_cell(&mut self, column: u16, row: u16, symbol: &str, style: ContentStyle) {
+        let Some(index) = self.index(column, row) else {
+            return;
+        };
+
+        if self.cells[index].symbol.is_empty() && index > 0 {
+            self.cells[index - 1].symbol = " ".to_string();
+        }
+        if text::width(&self.cells[index].symbol) == 2 {
+            if let Some(next_index) = self.index(column.saturating_add(1), row) {
+                self.cells[next_index].symbol = " ".to_string();
+            }
+        }
+
+        self.cells[index] = Cell {
+            symbol: symbol.to_string(),
+            style,
+        };
+    }
+
+    pub fn print(&mut self, text: &str) {
+        self.print_styled(text, ContentStyle::new());
+    }
+
+    /// Writes the text a grapheme per cell, wide graphemes take two cells.
+    pub fn print_styled(&mut self, text: &str, style: ContentStyle) {
+        for grapheme in text.graphemes(true) {
+            let width = text::grapheme_width(grapheme);
+            // Graphemes without a width, like control characters, are not shown.
+            if width == 0 {
+                continue;
+            }
+
+            // A wide grapheme at the end of the row is cut off as a whole.
+            let (column, row) = self.cursor;
+            if usize::from(column) + width <= usize::from(self.width) {
+                self.set_cell(column, row, grapheme, style);
+                if width == 2 {
+                    self.set_cell(column + 1, row, "", style);
+                }
+            }
+            self.cursor.0 = column.saturating_add(u16::try_from(width).unwrap_or(u16::MAX));
+        }
+    }
+
+    pub fn move_to(&mut self, column: u16, row: u16) {
+        self.cursor = (column, row);
+        self.line_start = column;
+    }
+
+    /// Moves the cursor to the next line, at the column of the last move.
+    pub fn next_line(&mut self) {
+        self.cursor = (self.line_start, self.cursor.1.saturating_add(1));
+    }
+
+    /// The text of every row without trailing spaces, used to compare screens in tests.
+    #[cfg(test)]
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.height)
+            .map(|row| {
+                (0..self.width)
+                    .filter_map(|column| self.cell(column, row))
+                    .map(|cell| cell.symbol.as_str())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    /// Writes the cells that changed since the previous buffer to the terminal,
+    /// every cell is written when there is no previous buffer or its size is different.
+    pub fn render<W: Write>(&self, previous: Option<&Buffer>, w: &mut W) -> io::Result<()> {
+        let previous = previous.filter(|x| x.width == self.width && x.height == self.height);
+        let is_changed =
+            |column, row| previous.is_none_or(|x| x.cell(column, row) != self.cell(column, row));
+
+        for row in 0..self.height {
+            let mut column = 0;
+            while column < self.width {
+                if !is_changed(column, row) {
+                    column += 1;
+                    continue;
+                }
+
+                // Changed cells next to each other are written together, starting at the first half
+                // of a wide grapheme.
+                let mut start = column;
+                if self.cell(start, row).is_some_and(|x| x.symbol.is_empty()) {
+                    start = start.saturating_sub(1);
+                }
+                while column < self.width && is_changed(column, row) {
+                    column += 1;
+                }
+                queue!(w, cursor::MoveTo(start, row))?;
+                print_cells(w, (start..column).filter_map(|x| self.cell(x, row)))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Cells with the same style are printed together.
+fn print_cells<'a, W: Write>(w: &mut W, cells: impl Iterator<Item = &'a Cell>) -> io::Result<()> {
+    let mut text = String::new();
+    let mut text_style = ContentStyle::new();
+    for cell in cells {
+        if cell.style != text_style && !text.is_empty() {
+            queue!(w, style::PrintStyledContent(text_style.apply(&text)))?;
+            text.clear();
+        }
+        text_style = cell.style;
+        text.push_str(&cell.symbol);
+    }
+    queue!(w, style::PrintStyledContent(text_style.apply(&text)))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Color;
+
+    use super::*;
+
+    #[test]
+    fn text_is_written_at_the_cursor_and_cut_off_at_the_edges() {
+        let mut buffer = Buffer::new(5, 2);
+        let style = ContentStyle {
+            foreground_color: Some(Color::Blue),
+            ..ContentStyle::new()
+        };
+
+        buffer.print("ab");
+        buffer.print_styled("cdefg", style);
+        buffer.next_line();
+        buffer.print("x");
+        buffer.next_line();
+        buffer.print("hidden");
+
+        assert_eq!(vec!["abcde", "x"], buffer.lines());
+        assert_eq!(Some(style), buffer.cell(2, 0).map(|x| x.style));
+        assert_eq!(None, buffer.cell(5, 0));
+
+        buffer.move_to(3, 0);
+        buffer.next_line();
+        buffer.print("y");
+        assert_eq!("x  y", buffer.lines()[1]);
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let mut previous = Buffer::new(5, 2);
+        previous.print("abc");
+        let mut buffer = Buffer::new(5, 2);
+        buffer.print("abc");
+
+        let mut output = vec![];
+        buffer.render(Some(&previous), &mut output).unwrap();
+        assert!(output.is_empty());
+
+        buffer.next_line();
+        buffer.print("  xy");
+        buffer.render(Some(&previous), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        // The cursor is moved to the third column of the second row.
+        assert!(output.starts_with("\u{1b}[2;3H"));
+        assert!(output.contains("xy"));
+        assert!(!output.contains("abc"));
+
+        // A buffer of another size is written completely.
+        let mut output = vec![];
+        buffer
+            .render(Some(&Buffer::new(4, 2)), &mut output)
+            .unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("abc"));
+    }
+
+    #[test]
+    fn wide_graphemes_take_two_cells() {
+        let mut buffer = Buffer::new(5, 2);
+
+        buffer.print("日本e\u{301}");
+        buffer.next_line();
+        // The second wide character does not fit at the end of the row.
+        buffer.print("abc日本");
+
+        assert_eq!(vec!["日本e\u{301}", "abc日"], buffer.lines());
+        assert_eq!(Some(""), buffer.cell(1, 0).map(|x| x.symbol.as_str()));
+
+        // Overwriting the second half of a wide character clears the first half.
+        buffer.move_to(1, 0);
+        buffer.print("x");
+        assert_eq!(" x本e\u{301}", buffer.lines()[0]);
+    }
+
+    #[test]
+    fn changes_in_the_second_half_of_a_wide_grapheme_write_the_whole_grapheme() {
+        let mut previous = Buffer::new(4, 1);
+        previous.print("日本");
+        let mut buffer = Buffer::new(4, 1);
+        buffer.print("日");
+        buffer.move_to(3, 0);
+        buffer.print("x");
+
+        let mut output = vec![];
+        buffer.render(Some(&previous), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        // The cursor is moved to the third column, where '本' started.
+        assert!(output.starts_with("\u{1b}[1;3H"));
+        assert!(!output.contains('日'));
+    }
+}
@@ -0,0 +1,552 @@
+use std::{error::Error, fmt};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::settings::Settings;
+
+use super::text;
+
+/// The named actions keys can be bound to.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Top,
+    Bottom,
+    NextMatch,
+    PreviousMatch,
+    Copy,
+    Search,
+    ClearFilter,
+    Add,
+    Rename,
+    Delete,
+    Pin,
+    Sort,
+    TogglePrivacy,
+    Reveal,
+    Help,
+    Quit,
+}
+
+// Every action with its name in the settings, its description and its default keys.
+// Keys pressed one after the other are separated by spaces, like 'g g'.
+const ACTIONS: [(Action, &str, &str, &str); 22] = [
+    (Action::MoveUp, "move_up", "Move up", "k, Up"),
+    (Action::MoveDown, "move_down", "Move down", "j, Down"),
+    (Action::PageUp, "page_up", "Move a page up", "PageUp"),
+    (
+        Action::PageDown,
+        "page_down",
+        "Move a page down",
+        "PageDown",
+    ),
+    (
+        Action::HalfPageUp,
+        "half_page_up",
+        "Move half a page up",
+        "Ctrl-u",
+    ),
+    (
+        Action::HalfPageDown,
+        "half_page_down",
+        "Move half a page down",
+        "Ctrl-d",
+    ),
+    (
+        Action::Top,
+        "top",
+        "Move to the top, or to the line of the count",
+        "Home, g g",
+    ),
+    (
+        Action::Bottom,
+        "bottom",
+        "Move to the bottom, or to the line of the count",
+        "End, G",
+    ),
+    (
+        Action::NextMatch,
+        "next_match",
+        "Move to the next match of the search",
+        "n",
+    ),
+    (
+        Action::PreviousMatch,
+        "previous_match",
+        "Move to the previous match of the search",
+        "N",
+    ),
+    (
+        Action::Copy,
+        "copy",
+        "Copy the code, or collapse a group",
+        "Enter",
+    ),
+    (
+        Action::Search,
+        "search",
+        "Search, '#tag' filters by tag",
+        "/",
+    ),
+    (
+        Action::ClearFilter,
+        "clear_filter",
+        "Clear the tag filter",
+        "Esc",
+    ),
+    (Action::Add, "add", "Add an entry", "a"),
+    (Action::Rename, "rename", "Rename the entry", "r"),
+    (Action::Delete, "delete", "Delete the entry", "d"),
+    (Action::Pin, "pin", "Pin or unpin the entry", "p"),
+    (Action::Sort, "sort", "Switch the sort mode", "s"),
+    (
+        Action::TogglePrivacy,
+        "toggle_privacy",
+        "Mask or show the codes",
+        "m",
+    ),
+    (
+        Action::Reveal,
+        "reveal",
+        "Show the masked code of the entry for a few seconds",
+        "v",
+    ),
+    (Action::Help, "help", "Show this help", "?"),
+    (Action::Quit, "quit", "Quit", "q"),
+];
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum KeymapError {
+    InvalidKey(String),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                KeymapError::InvalidKey(message) => message,
+            }
+        )
+    }
+}
+
+impl Error for KeymapError {}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn from_event(event: &KeyEvent) -> Self {
+        // Shift is part of the character itself, 'G' is typed with shift.
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    /// Parses keys like `j`, `G`, `Enter`, `PageDown` and `Ctrl-d`.
+    fn parse(text: &str) -> Option<Self> {
+        let (modifiers, name) = match text.split_once('-') {
+            Some(("Ctrl", name)) if !name.is_empty() => (KeyModifiers::CONTROL, name),
+            Some(("Alt", name)) if !name.is_empty() => (KeyModifiers::ALT, name),
+            _ => (KeyModifiers::NONE, text),
+        };
+
+        let code = match name {
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) => KeyCode::Char(char),
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(char) => write!(f, "{char}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// An action together with the count typed before its keys, like `5j`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct CountedAction {
+    pub action: Action,
+    pub count: Option<usize>,
+}
+
+impl CountedAction {
+    /// The count, actions without a count are done once.
+    pub fn count_or_one(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+}
+
+// A binding is a sequence of keys, most bindings are a single key.
+type Binding = Vec<Key>;
+
+/// Maps key events to actions, the keys of an action can be changed with the
+/// `key.<action>` setting, for example `key.move_down = j, Down, Ctrl-n`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<Binding>)>,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Lookup {
+    Action(Action),
+    // The keys are the start of a binding, so more keys are needed.
+    Prefix,
+    Unbound,
+}
+
+fn parse_keys(keys: &str) -> Result<Vec<Binding>, KeymapError> {
+    keys.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|binding| {
+            binding
+                .split_whitespace()
+                .map(|x| {
+                    Key::parse(x)
+                        .ok_or_else(|| KeymapError::InvalidKey(format!("Unknown key '{x}'.")))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: ACTIONS
+                .iter()
+                .map(|(action, _, _, keys)| {
+                    (
+                        *action,
+                        parse_keys(keys).expect("Could not parse default keys."),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn from_settings(settings: &Settings) -> Result<Self, KeymapError> {
+        let mut keymap = Self::default();
+
+        for ((_, keys), (_, name, _, _)) in keymap.bindings.iter_mut().zip(ACTIONS) {
+            if let Some(value) = settings.get(&format!("key.{name}")) {
+                *keys = parse_keys(value).map_err(|err| {
+                    KeymapError::InvalidKey(format!(
+                        "Invalid value for setting 'key.{name}': {err}"
+                    ))
+                })?;
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    // If keys are bound to multiple actions the first one is used.
+    fn lookup(&self, keys: &[Key]) -> Lookup {
+        let bindings = || {
+            self.bindings
+                .iter()
+                .flat_map(|(action, x)| x.iter().map(move |x| (action, x)))
+        };
+
+        if let Some((action, _)) = bindings().find(|(_, binding)| binding.as_slice() == keys) {
+            Lookup::Action(*action)
+        } else if bindings().any(|(_, binding)| binding.starts_with(keys)) {
+            Lookup::Prefix
+        } else {
+            Lookup::Unbound
+        }
+    }
+
+    /// A line for every action with its keys and description.
+    pub fn help_lines(&self) -> Vec<String> {
+        let keys = self
+            .bindings
+            .iter()
+            .map(|(_, bindings)| {
+                bindings
+                    .iter()
+                    .map(|binding| {
+                        binding
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .collect::<Vec<_>>();
+        let keys_width = keys.iter().map(|x| text::width(x)).max().unwrap_or(0);
+
+        keys.iter()
+            .zip(ACTIONS)
+            .map(|(keys, (_, _, description, _))| {
+                format!("{}  {description}", text::pad(keys, keys_width))
+            })
+            .collect()
+    }
+}
+
+/// The keys pressed so far, when keys are pressed one after the other for a single action.
+/// Digits typed before the keys are the count of the action.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct PendingKeys {
+    count: Option<usize>,
+    keys: Vec<Key>,
+}
+
+impl PendingKeys {
+    /// Adds the key to the pending keys, when they complete a binding its action is returned
+    /// and the pending keys are cleared. Keys that can not complete a binding are dropped.
+    pub fn push(&mut self, keymap: &Keymap, event: &KeyEvent) -> Option<CountedAction> {
+        let key = Key::from_event(event);
+
+        // A leading zero is not a count, the same as in vim.
+        if let (KeyCode::Char(digit @ '0'..='9'), true) = (key.code, self.keys.is_empty()) {
+            if key.modifiers.is_empty() && (digit != '0' || self.count.is_some()) {
+                let digit = digit.to_digit(10).map_or(0, |x| x as usize);
+                self.count = Some(
+                    self.count
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+                return None;
+            }
+        }
+
+        self.keys.push(key);
+        match keymap.lookup(&self.keys) {
+            Lookup::Action(action) => {
+                let count = self.count;
+                self.clear();
+                Some(CountedAction { action, count })
+            }
+            Lookup::Prefix => None,
+            Lookup::Unbound => {
+                self.clear();
+                None
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char(char: char) -> KeyEvent {
+        KeyEvent::from(KeyCode::Char(char))
+    }
+
+    // The action of a single key press.
+    fn action(keymap: &Keymap, event: &KeyEvent) -> Option<Action> {
+        PendingKeys::default().push(keymap, event).map(|x| x.action)
+    }
+
+    #[test]
+    fn default_keys_are_mapped_to_actions() {
+        let keymap = Keymap::default();
+
+        let assertions = [
+            (KeyEvent::from(KeyCode::Char('j')), Some(Action::MoveDown)),
+            (KeyEvent::from(KeyCode::Down), Some(Action::MoveDown)),
+            (KeyEvent::from(KeyCode::Up), Some(Action::MoveUp)),
+            (KeyEvent::from(KeyCode::PageDown), Some(Action::PageDown)),
+            (KeyEvent::from(KeyCode::Char('q')), Some(Action::Quit)),
+            (
+                KeyEvent::new(KeyCode::Char('?'), KeyModifiers::SHIFT),
+                Some(Action::Help),
+            ),
+            (
+                KeyEvent::new(KeyCode::Char('j'), KeyModifiers::CONTROL),
+                None,
+            ),
+            (KeyEvent::from(KeyCode::Char('x')), None),
+        ];
+
+        for (event, expected) in assertions {
+            assert_eq!(expected, action(&keymap, &event));
+        }
+    }
+
+    #[test]
+    fn keys_can_be_changed_in_settings() {
+        let settings = Settings::parse("key.move_down = n, Ctrl-n\nkey.quit = Ctrl-c").unwrap();
+        let keymap = Keymap::from_settings(&settings).unwrap();
+
+        assert_eq!(
+            Some(Action::MoveDown),
+            action(
+                &keymap,
+                &KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL)
+            )
+        );
+        assert_eq!(None, action(&keymap, &KeyEvent::from(KeyCode::Char('j'))));
+        assert_eq!(
+            Some(Action::Quit),
+            action(
+                &keymap,
+                &KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_key_in_settings_results_in_error() {
+        let settings = Settings::parse("key.move_down = Hyper-j").unwrap();
+
+        assert!(matches!(
+            Keymap::from_settings(&settings),
+            Err(KeymapError::InvalidKey(message)) if message.contains("key.move_down")
+        ));
+    }
+
+    #[test]
+    fn help_lists_keys_of_every_action() {
+        let help_lines = Keymap::default().help_lines();
+
+        assert_eq!(ACTIONS.len(), help_lines.len());
+        assert_eq!("k, Up      Move up", help_lines[0]);
+        assert_eq!("PageDown   Move a page down", help_lines[3]);
+        assert_eq!(
+            "Home, g g  Move to the top, or to the line of the count",
+            help_lines[6]
+        );
+    }
+
+    #[test]
+    fn count_is_typed_before_keys() {
+        let keymap = Keymap::default();
+        let mut pending_keys = PendingKeys::default();
+
+        assert_eq!(None, pending_keys.push(&keymap, &char('1')));
+        assert_eq!(None, pending_keys.push(&keymap, &char('2')));
+        assert_eq!(
+            Some(CountedAction {
+                action: Action::MoveDown,
+                count: Some(12)
+            }),
+            pending_keys.push(&keymap, &char('j'))
+        );
+
+        // The count is cleared after the action.
+        assert_eq!(
+            Some(CountedAction {
+                action: Action::MoveDown,
+                count: None
+            }),
+            pending_keys.push(&keymap, &char('j'))
+        );
+    }
+
+    #[test]
+    fn leading_zero_is_not_a_count() {
+        let keymap = Keymap::default();
+        let mut pending_keys = PendingKeys::default();
+
+        assert_eq!(None, pending_keys.push(&keymap, &char('0')));
+        assert_eq!(PendingKeys::default(), pending_keys);
+
+        pending_keys.push(&keymap, &char('1'));
+        pending_keys.push(&keymap, &char('0'));
+        assert_eq!(
+            Some(10),
+            pending_keys
+                .push(
+                    &keymap,
+                    &KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT)
+                )
+                .and_then(|x| x.count)
+        );
+    }
+
+    #[test]
+    fn keys_can_be_pressed_one_after_the_other() {
+        let keymap = Keymap::default();
+        let mut pending_keys = PendingKeys::default();
+
+        assert_eq!(None, pending_keys.push(&keymap, &char('g')));
+        assert_eq!(
+            Some(Action::Top),
+            pending_keys.push(&keymap, &char('g')).map(|x| x.action)
+        );
+    }
+
+    #[test]
+    fn unbound_keys_clear_pending_keys() {
+        let keymap = Keymap::default();
+        let mut pending_keys = PendingKeys::default();
+
+        pending_keys.push(&keymap, &char('3'));
+        pending_keys.push(&keymap, &char('g'));
+        assert_eq!(None, pending_keys.push(&keymap, &char('x')));
+        assert_eq!(PendingKeys::default(), pending_keys);
+
+        // The next key starts over, without the count.
+        assert_eq!(
+            Some(CountedAction {
+                action: Action::MoveUp,
+                count: None
+            }),
+            pending_keys.push(&keymap, &char('k'))
+        );
+    }
+}
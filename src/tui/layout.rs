@@ -0,0 +1,89 @@
+/// The area of the screen an element is drawn in.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(self, column: u16, row: u16) -> bool {
+        column >= self.x
+            && column - self.x < self.width
+            && row >= self.y
+            && row - self.y < self.height
+    }
+}
+
+/// Splits the area into rows of the given heights from top to bottom,
+/// the areas without a height share the rows that are left.
+pub fn split_vertically(area: Rect, heights: &[Option<u16>]) -> Vec<Rect> {
+    let fixed_height: u16 = heights.iter().flatten().sum();
+    let flexible_count = heights.iter().filter(|x| x.is_none()).count();
+    let flexible_height = match u16::try_from(flexible_count) {
+        Ok(count) if count > 0 => area.height.saturating_sub(fixed_height) / count,
+        _ => 0,
+    };
+
+    let mut y = area.y;
+    heights
+        .iter()
+        .map(|height| {
+            let bottom = area.y.saturating_add(area.height);
+            let height = height
+                .unwrap_or(flexible_height)
+                .min(bottom.saturating_sub(y));
+            let rect = Rect::new(area.x, y, area.width, height);
+            y = y.saturating_add(height);
+            rect
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_heights_are_kept_and_the_rest_is_shared() {
+        let area = Rect::new(0, 0, 80, 24);
+
+        assert_eq!(
+            vec![Rect::new(0, 0, 80, 1), Rect::new(0, 1, 80, 23)],
+            split_vertically(area, &[Some(1), None])
+        );
+        assert_eq!(
+            vec![
+                Rect::new(0, 0, 80, 2),
+                Rect::new(0, 2, 80, 11),
+                Rect::new(0, 13, 80, 11)
+            ],
+            split_vertically(area, &[Some(2), None, None])
+        );
+        // Areas below the bottom of the screen are empty.
+        assert_eq!(
+            vec![Rect::new(0, 0, 80, 24), Rect::new(0, 24, 80, 0)],
+            split_vertically(area, &[Some(30), None])
+        );
+    }
+
+    #[test]
+    fn rect_contains_its_cells() {
+        let rect = Rect::new(2, 1, 3, 2);
+
+        assert!(rect.contains(2, 1));
+        assert!(rect.contains(4, 2));
+        assert!(!rect.contains(5, 2));
+        assert!(!rect.contains(2, 0));
+    }
+}
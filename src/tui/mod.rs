@@ -1,76 +1,761 @@
+mod buffer;
+mod keymap;
+mod layout;
+mod terminal_guard;
+mod text;
+mod theme;
 mod totp_line_paragraph;
 mod totp_list_view;
 mod widgets;
 
+pub use buffer::Buffer;
+pub use keymap::{Action, CountedAction, Keymap, PendingKeys};
+pub use layout::Rect;
+pub use terminal_guard::TerminalGuard;
+pub use theme::{is_monochrome_terminal, Theme};
 pub use totp_line_paragraph::TotpLineParagraph;
 pub use totp_list_view::TotpListView;
 
 use std::{
     error::Error,
-    io::{Stdout, Write},
-    time::Duration,
+    io::Write,
+    time::{Duration, SystemTime},
 };
 
 use crossterm::{
-    cursor,
-    event::{self, poll, Event, KeyCode},
-    execute, queue, style, terminal,
+    event::{self, poll, Event, MouseEvent},
+    terminal,
 };
 
 pub trait Display {
-    fn display(&mut self, w: &mut Stdout) -> Result<(), Box<dyn Error>>;
+    // Elements are drawn one below the other, each in the area it is given.
+    fn display(&mut self, buffer: &mut Buffer, area: Rect);
+
+    // The number of rows the element needs, elements without a height share the rows that are left.
+    fn height(&self) -> Option<u16> {
+        None
+    }
 }
 
 pub trait HandleEvent {
-    fn handle_event(&mut self, event: &Event);
+    // The action is the one the keys are bound to in the keymap, with the count typed before them.
+    fn handle_event(&mut self, event: &Event, action: Option<CountedAction>);
+
+    // Elements capturing input, for example while typing in a prompt,
+    // receive every key press without an action, so keys like 'q' do not quit the application.
+    fn captures_input(&self) -> bool {
+        false
+    }
 }
 
 pub trait Refresh {
-    fn refresh(&mut self);
+    // The time is passed in, so the codes can be drawn for a fixed time in tests.
+    fn refresh(&mut self, time: SystemTime);
+
+    // The time the element changes next without any input, the screen is drawn again at that time.
+    fn next_refresh(&self, _time: SystemTime) -> Option<SystemTime> {
+        None
+    }
 }
 
-pub trait Element: Display + Refresh + HandleEvent {}
+pub trait Lock {
+    // The TUI locks after a while without input, elements drop their secrets from memory.
+    fn lock(&mut self) {}
 
-pub fn start(
-    w: &mut Stdout,
-    mut elements: Vec<Box<dyn Element>>,
-    pool_interval: u64,
-) -> Result<(), Box<dyn Error>> {
-    execute!(w, terminal::EnterAlternateScreen, cursor::Hide)?;
-    terminal::enable_raw_mode()?;
+    // Elements load their secrets again, the TUI stays locked when this fails.
+    fn unlock(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
 
-    'main: loop {
-        queue!(w, cursor::MoveTo(0, 0),)?;
+pub trait Element: Display + Refresh + HandleEvent + Lock {}
 
-        for element in &mut elements {
-            element.refresh();
-            element.display(w)?;
+fn display_help(buffer: &mut Buffer, keymap: &Keymap) {
+    for line in keymap.help_lines() {
+        buffer.print(&line);
+        buffer.next_line();
+    }
+    buffer.next_line();
+    buffer.print("Press any key to close.");
+}
 
-            w.flush()?;
+fn display_lock_screen(buffer: &mut Buffer, error: Option<&str>) {
+    buffer.print("Locked after a while without input.");
+    buffer.next_line();
+    buffer.print("Press any key to continue.");
+    if let Some(error) = error {
+        buffer.next_line();
+        buffer.next_line();
+        buffer.print(error);
+    }
+}
+
+// The elements and the state of the TUI, without the terminal, so it can be driven by tests.
+struct App<'a> {
+    elements: Vec<Box<dyn Element>>,
+    keymap: &'a Keymap,
+    // The help is shown over the elements, until any key is pressed.
+    show_help: bool,
+    pending_keys: PendingKeys,
+    // The area each element was drawn in.
+    areas: Vec<Rect>,
+    // The TUI locks when there is no input for this long.
+    lock_timeout: Option<Duration>,
+    last_activity: Option<SystemTime>,
+    locked: bool,
+    // The reason unlocking failed, shown on the lock screen.
+    unlock_error: Option<String>,
+}
+
+impl<'a> App<'a> {
+    fn new(elements: Vec<Box<dyn Element>>, keymap: &'a Keymap) -> Self {
+        Self {
+            areas: vec![Rect::default(); elements.len()],
+            elements,
+            keymap,
+            show_help: false,
+            pending_keys: PendingKeys::default(),
+            lock_timeout: None,
+            last_activity: None,
+            locked: false,
+            unlock_error: None,
         }
+    }
 
-        if poll(Duration::from_millis(pool_interval))? {
-            let event = event::read()?;
+    fn with_lock_timeout(mut self, lock_timeout: Option<Duration>) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
 
-            for element in &mut elements {
-                element.handle_event(&event);
+    // The time the TUI locks, `None` when it is locked already or never locks.
+    fn lock_time(&self) -> Option<SystemTime> {
+        match (self.lock_timeout, self.last_activity) {
+            (Some(lock_timeout), Some(last_activity)) if !self.locked => {
+                Some(last_activity + lock_timeout)
             }
+            _ => None,
+        }
+    }
+
+    fn lock(&mut self) {
+        self.locked = true;
+        self.show_help = false;
+        self.pending_keys.clear();
+        for element in &mut self.elements {
+            element.lock();
+        }
+    }
 
-            if event == Event::Key(KeyCode::Char('q').into()) {
-                break 'main;
+    fn unlock(&mut self, time: SystemTime) {
+        for element in &mut self.elements {
+            if let Err(err) = element.unlock() {
+                self.unlock_error = Some(format!("Error: {err}"));
+                return;
             }
         }
+        self.locked = false;
+        self.unlock_error = None;
+        self.last_activity = Some(time);
     }
 
-    // Cleanup and reset the terminal when the user quits.
-    execute!(
-        w,
-        style::ResetColor,
-        cursor::Show,
-        terminal::LeaveAlternateScreen
-    )?;
+    fn render(&mut self, time: SystemTime, width: u16, height: u16) -> Buffer {
+        let mut buffer = Buffer::new(width, height);
+        self.last_activity.get_or_insert(time);
+
+        if self.lock_time().is_some_and(|lock_time| time >= lock_time) {
+            self.lock();
+        }
+        if self.locked {
+            display_lock_screen(&mut buffer, self.unlock_error.as_deref());
+            return buffer;
+        }
+
+        // Elements are refreshed while the help is shown, so they are up to date when it closes.
+        for element in &mut self.elements {
+            element.refresh(time);
+        }
 
-    terminal::disable_raw_mode()?;
+        if self.show_help {
+            display_help(&mut buffer, self.keymap);
+        } else {
+            let heights = self
+                .elements
+                .iter()
+                .map(|element| element.height())
+                .collect::<Vec<_>>();
+            self.areas = layout::split_vertically(Rect::new(0, 0, width, height), &heights);
+
+            for (element, area) in self.elements.iter_mut().zip(&self.areas) {
+                element.display(&mut buffer, *area);
+            }
+        }
+
+        buffer
+    }
+
+    // The earliest time any element changes or the TUI locks, `None` when nothing changes without input.
+    fn next_deadline(&self, time: SystemTime) -> Option<SystemTime> {
+        if self.locked {
+            return None;
+        }
+
+        self.elements
+            .iter()
+            .filter_map(|element| element.next_refresh(time))
+            .chain(self.lock_time())
+            .min()
+    }
+
+    // Returns false when the application should quit.
+    // The time is when the event arrived, input is counted as activity at that time.
+    fn handle_event(&mut self, event: &Event, time: SystemTime) -> bool {
+        // Any key unlocks the TUI, without doing its action.
+        if self.locked {
+            if let Event::Key(_) = event {
+                self.unlock(time);
+            }
+            return true;
+        }
+
+        if let Event::Key(_) | Event::Mouse(_) = event {
+            self.last_activity = Some(time);
+        }
+
+        if self.show_help {
+            if let Event::Key(_) = event {
+                self.show_help = false;
+            }
+            return true;
+        }
+
+        // Mouse events only go to the element under the pointer, relative to the top left of its area.
+        if let Event::Mouse(mouse) = *event {
+            if let Some((element, area)) = self
+                .elements
+                .iter_mut()
+                .zip(&self.areas)
+                .find(|(_, area)| area.contains(mouse.column, mouse.row))
+            {
+                let event = Event::Mouse(MouseEvent {
+                    column: mouse.column - area.x,
+                    row: mouse.row - area.y,
+                    ..mouse
+                });
+                element.handle_event(&event, None);
+            }
+            return true;
+        }
+
+        // The next frame is drawn at the new size.
+        if let Event::Resize(..) = event {
+            return true;
+        }
+
+        let captures_input = self.elements.iter().any(|element| element.captures_input());
+        if captures_input {
+            self.pending_keys.clear();
+        }
+        let action = match event {
+            Event::Key(key) if !captures_input => self.pending_keys.push(self.keymap, key),
+            _ => None,
+        };
+
+        match action.map(|x| x.action) {
+            Some(Action::Quit) => return false,
+            Some(Action::Help) => self.show_help = true,
+            _ => {
+                for element in &mut self.elements {
+                    element.handle_event(event, action);
+                }
+            }
+        }
+
+        true
+    }
+}
+
+// How long to wait for input when no element changes by itself.
+const IDLE_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// Runs the TUI until it is quit, after `lock_timeout` without input the screen is blanked
+/// and the elements drop their secrets until a key is pressed.
+/// The terminal is restored when the TUI quits, fails, panics or the process gets a signal.
+pub fn start<W: Write>(
+    w: &mut W,
+    elements: Vec<Box<dyn Element>>,
+    keymap: &Keymap,
+    lock_timeout: Option<Duration>,
+) -> Result<(), Box<dyn Error>> {
+    terminal_guard::install_panic_hook();
+    let signals = terminal_guard::handle_signals()?;
+    let mut guard = TerminalGuard::enter(w)?;
+
+    let result = run(guard.writer(), elements, keymap, lock_timeout);
+    signals.close();
+
+    result
+}
+
+fn run<W: Write>(
+    w: &mut W,
+    elements: Vec<Box<dyn Element>>,
+    keymap: &Keymap,
+    lock_timeout: Option<Duration>,
+) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(elements, keymap).with_lock_timeout(lock_timeout);
+    // The screen as it is in the terminal, only the cells that differ from it are written.
+    let mut screen: Option<Buffer> = None;
+
+    loop {
+        let (width, height) = terminal::size()?;
+        let now = SystemTime::now();
+        let buffer = app.render(now, width, height);
+        buffer.render(screen.as_ref(), w)?;
+        w.flush()?;
+        screen = Some(buffer);
+
+        // Sleeps until the next change, like the next second of the countdown or the next code,
+        // unless there is input before that.
+        let timeout = app.next_deadline(now).map_or(IDLE_TIMEOUT, |deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        });
+        if poll(timeout)? {
+            let event = event::read()?;
+            // The terminal might have moved the text around, so the next frame is written completely.
+            if let Event::Resize(..) = event {
+                screen = None;
+            }
+            if !app.handle_event(&event, SystemTime::now()) {
+                return Ok(());
+            }
+        }
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, rc::Rc};
+
+    use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEventKind};
+
+    use crate::{state::State, totp::Totp};
+
+    use super::*;
+
+    const MARCH_14_2020: u64 = 1_584_140_400;
+    const WIDTH: u16 = 40;
+    const HEIGHT: u16 = 8;
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(MARCH_14_2020 + seconds)
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(code.into())
+    }
+
+    fn click(column: u16, row: u16) -> Event {
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    }
+
+    // The names of the entries copied to the clipboard.
+    type Copied = Rc<RefCell<Vec<String>>>;
+
+    fn elements(directory: &tempfile::TempDir) -> (Vec<Box<dyn Element>>, Copied) {
+        let copied = Rc::new(RefCell::new(vec![]));
+        let copied_names = Rc::clone(&copied);
+        let totps = vec![
+            Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30),
+            Totp::new("Widget Co", "MFRGGZDF", 6, 30),
+        ];
+
+        let elements: Vec<Box<dyn Element>> = vec![
+            Box::new(TotpLineParagraph::new()),
+            Box::new(TotpListView::new(
+                at(0),
+                directory.path().join("totp.txt"),
+                6,
+                30,
+                totps,
+                Box::new(move |totp: &Totp| copied_names.borrow_mut().push(totp.name.clone())),
+                State::load(directory.path().join("state.txt")).unwrap(),
+            )),
+        ];
+
+        (elements, copied)
+    }
+
+    #[test]
+    fn screen_shows_countdown_and_codes() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let (elements, _) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+
+        let buffer = app.render(at(5), WIDTH, HEIGHT);
+
+        assert_eq!(
+            vec![
+                "05/30",
+                "Acme Inc. | 396507",
+                "Widget Co | 711370",
+                "",
+                "",
+                "",
+                "",
+                ""
+            ],
+            buffer.lines()
+        );
+        assert_eq!(Theme::default().selected, buffer.cell(0, 1).unwrap().style);
+        assert_eq!(
+            Theme::default().countdown_critical,
+            app.render(at(27), WIDTH, HEIGHT).cell(0, 0).unwrap().style
+        );
+    }
+
+    #[test]
+    fn scripted_keys_search_copy_and_show_help() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let (elements, copied) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+
+        for event in [
+            key(KeyCode::Char('/')),
+            key(KeyCode::Char('w')),
+            key(KeyCode::Char('i')),
+        ] {
+            assert!(app.handle_event(&event, at(0)));
+        }
+        let buffer = app.render(at(5), WIDTH, HEIGHT);
+        assert_eq!("/wi", buffer.lines()[4]);
+        assert_eq!(Theme::default().selected, buffer.cell(0, 2).unwrap().style);
+
+        app.handle_event(&key(KeyCode::Enter), at(5));
+        app.handle_event(&key(KeyCode::Enter), at(5));
+        assert_eq!(vec!["Widget Co"], *copied.borrow());
+        assert_eq!(
+            "Widget Co | 711370 *",
+            app.render(at(6), WIDTH, HEIGHT).lines()[2]
+        );
+
+        app.handle_event(&key(KeyCode::Char('?')), at(6));
+        let buffer = app.render(at(7), WIDTH, HEIGHT);
+        assert_eq!(keymap.help_lines()[0], buffer.lines()[0]);
+
+        // Any key closes the help, without doing its action.
+        app.handle_event(&key(KeyCode::Char('k')), at(7));
+        assert_eq!("07/30", app.render(at(7), WIDTH, HEIGHT).lines()[0]);
+        assert_eq!(
+            Theme::default().selected,
+            app.render(at(7), WIDTH, HEIGHT).cell(0, 2).unwrap().style
+        );
+
+        assert!(!app.handle_event(&key(KeyCode::Char('q')), at(7)));
+    }
+
+    #[test]
+    fn private_mode_masks_codes_until_revealed() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let (elements, copied) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+
+        app.handle_event(&key(KeyCode::Char('m')), at(0));
+        assert_eq!(
+            vec!["Acme Inc. | ••• •••", "Widget Co | ••• •••"],
+            app.render(at(5), WIDTH, HEIGHT).lines()[1..3]
+        );
+
+        // Only the selected entry is revealed, for a few seconds.
+        app.handle_event(&key(KeyCode::Char('v')), at(5));
+        assert_eq!(
+            vec!["Acme Inc. | 396507", "Widget Co | ••• •••"],
+            app.render(at(9), WIDTH, HEIGHT).lines()[1..3]
+        );
+        assert_eq!(
+            Some(at(10)),
+            app.next_deadline(at(9) + Duration::from_millis(500))
+        );
+        assert_eq!(
+            "Acme Inc. | ••• •••",
+            app.render(at(10), WIDTH, HEIGHT).lines()[1]
+        );
+
+        // Masked codes are still copied.
+        app.handle_event(&key(KeyCode::Char('j')), at(10));
+        app.handle_event(&key(KeyCode::Enter), at(10));
+        assert_eq!(vec!["Widget Co"], *copied.borrow());
+    }
+
+    #[test]
+    fn locks_without_input_until_a_key_is_pressed() {
+        let directory = tempfile::tempdir().unwrap();
+        let config_file_path = directory.path().join("totp.txt");
+        fs::write(
+            &config_file_path,
+            "otpauth://totp/Acme:Acme?secret=GZMWV5JLOMNI2XJL&issuer=Acme\n\
+             otpauth://totp/Widget:Widget?secret=MFRGGZDF&issuer=Widget\n",
+        )
+        .unwrap();
+        let keymap = Keymap::default();
+        let (elements, _) = elements(&directory);
+        let mut app = App::new(elements, &keymap).with_lock_timeout(Some(Duration::from_secs(20)));
+
+        app.render(at(5), WIDTH, HEIGHT);
+        app.handle_event(&key(KeyCode::Char('j')), at(5));
+        assert_eq!(
+            Some(at(25)),
+            app.next_deadline(at(24) + Duration::from_millis(500))
+        );
+        assert_eq!(
+            "Acme Inc. | 396507",
+            app.render(at(24), WIDTH, HEIGHT).lines()[1]
+        );
+
+        let buffer = app.render(at(25), WIDTH, HEIGHT);
+        assert_eq!("Locked after a while without input.", buffer.lines()[0]);
+        assert!(!buffer.lines().iter().any(|x| x.contains("396507")));
+        assert_eq!(None, app.next_deadline(at(25)));
+
+        // The key only unlocks, the entries are loaded from the secrets file again.
+        assert!(app.handle_event(&key(KeyCode::Char('q')), at(25)));
+        let buffer = app.render(at(26), WIDTH, HEIGHT);
+        assert_eq!(
+            vec!["Acme   | 396507", "Widget | 711370"],
+            buffer.lines()[1..3]
+        );
+        assert_eq!(Theme::default().selected, buffer.cell(0, 2).unwrap().style);
+
+        // The TUI stays locked when the secrets can not be loaded.
+        fs::remove_file(&config_file_path).unwrap();
+        app.render(at(200), WIDTH, HEIGHT);
+        app.handle_event(&key(KeyCode::Enter), at(200));
+        let lines = app.render(at(201), WIDTH, HEIGHT).lines();
+        assert_eq!("Locked after a while without input.", lines[0]);
+        assert!(lines[3].starts_with("Error: "));
+    }
+
+    #[test]
+    fn unlocking_counts_as_activity_at_the_time_of_the_key() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(
+            directory.path().join("totp.txt"),
+            "otpauth://totp/Acme:Acme?secret=GZMWV5JLOMNI2XJL&issuer=Acme\n",
+        )
+        .unwrap();
+        let keymap = Keymap::default();
+        let (elements, _) = elements(&directory);
+        let mut app = App::new(elements, &keymap).with_lock_timeout(Some(Duration::from_secs(20)));
+
+        app.render(at(0), WIDTH, HEIGHT);
+        app.render(at(20), WIDTH, HEIGHT);
+        // While locked, frames are only drawn when idle, long after the lock timeout.
+        app.render(at(80), WIDTH, HEIGHT);
+        app.handle_event(&key(KeyCode::Char('j')), at(135));
+
+        let lines = app.render(at(136), WIDTH, HEIGHT).lines();
+        assert_eq!("Acme | 150521", lines[1]);
+        assert_eq!(Some(at(155)), app.lock_time());
+    }
+
+    #[test]
+    fn tag_filter_follows_the_search_input() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let totps = vec![
+            Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30).with_tags(&["work"]),
+            Totp::new("Widget Co", "MFRGGZDF", 6, 30).with_tags(&["wolf"]),
+            Totp::new("Gizmo", "MFRGGZDF", 6, 30),
+        ];
+        let elements: Vec<Box<dyn Element>> = vec![Box::new(TotpListView::new(
+            at(0),
+            directory.path().join("totp.txt"),
+            6,
+            30,
+            totps,
+            Box::new(|_: &Totp| {}),
+            State::load(directory.path().join("state.txt")).unwrap(),
+        ))];
+        let mut app = App::new(elements, &keymap);
+        let names = |app: &mut App| {
+            app.render(at(0), WIDTH, HEIGHT)
+                .lines()
+                .into_iter()
+                .filter_map(|x| x.split(" |").next().map(str::trim).map(String::from))
+                .filter(|x| !x.is_empty() && !x.starts_with('/'))
+                .collect::<Vec<_>>()
+        };
+
+        for character in "/#work".chars() {
+            app.handle_event(&key(KeyCode::Char(character)), at(0));
+        }
+        assert_eq!(vec!["Acme Inc."], names(&mut app));
+
+        for _ in 0..2 {
+            app.handle_event(&key(KeyCode::Backspace), at(0));
+        }
+        assert_eq!(vec!["Acme Inc.", "Widget Co"], names(&mut app));
+
+        for _ in 0..3 {
+            app.handle_event(&key(KeyCode::Backspace), at(0));
+        }
+        assert_eq!(vec!["Acme Inc.", "Widget Co", "Gizmo"], names(&mut app));
+    }
+
+    #[test]
+    fn layout_follows_the_size_of_the_screen() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let (elements, _) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+
+        // Narrow screens show the code first and cut off the names.
+        assert_eq!(
+            vec!["05/30", "396507 Acme I…", "711370 Widget…"],
+            app.render(at(5), 16, 6).lines()[..3]
+        );
+
+        // Very wide screens continue the list in the next column.
+        assert_eq!(
+            format!("Acme Inc. | 396507{}Widget Co | 711370", " ".repeat(6)),
+            app.render(at(5), 130, 5).lines()[1]
+        );
+
+        // A resize is drawn on the next frame.
+        assert!(app.handle_event(&Event::Resize(40, 8), at(5)));
+        assert_eq!("Widget Co | 711370", app.render(at(5), 40, 8).lines()[2]);
+    }
+
+    #[test]
+    fn double_clicking_copies_only_when_the_same_entry_is_clicked_twice() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let (elements, copied) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+        app.render(at(5), 130, 5);
+
+        // Both entries are on the same row, in different columns.
+        app.handle_event(&click(2, 1), at(5));
+        app.handle_event(&click(26, 1), at(5));
+        assert!(copied.borrow().is_empty());
+        assert_eq!(
+            Theme::default().selected,
+            app.render(at(5), 130, 5).cell(26, 1).unwrap().style
+        );
+
+        app.handle_event(&click(26, 1), at(5));
+        assert_eq!(vec!["Widget Co"], *copied.borrow());
+    }
+
+    #[test]
+    fn codes_change_with_the_counter_and_the_loop_wakes_every_second() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let (elements, _) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+
+        let first_code = app.render(at(5), WIDTH, HEIGHT).lines()[1].clone();
+        assert_eq!(
+            Some(at(6)),
+            app.next_deadline(at(5) + Duration::from_millis(200))
+        );
+
+        // The codes change even when no frame is drawn at the start of the interval.
+        let next_code = app.render(at(31), WIDTH, HEIGHT).lines()[1].clone();
+        assert_ne!(first_code, next_code);
+        assert_eq!(next_code, app.render(at(59), WIDTH, HEIGHT).lines()[1]);
+    }
+
+    #[test]
+    fn spaces_are_removed_from_added_secrets() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("totp.txt");
+        fs::write(
+            &path,
+            "otpauth://totp/Acme Inc.:Acme Inc.?secret=GZMWV5JLOMNI2XJL&issuer=Acme Inc.\n",
+        )
+        .unwrap();
+        let keymap = Keymap::default();
+        let (elements, _) = elements(&directory);
+        let mut app = App::new(elements, &keymap);
+
+        app.handle_event(&key(KeyCode::Char('a')), at(0));
+        for character in "Foo\rmfrg gzdf\r".chars() {
+            app.handle_event(
+                &key(match character {
+                    '\r' => KeyCode::Enter,
+                    _ => KeyCode::Char(character),
+                }),
+                at(0),
+            );
+        }
+
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("otpauth://totp/Foo:Foo?secret=mfrggzdf&issuer=Foo"));
+    }
+
+    #[test]
+    fn list_scrolls_to_keep_the_selection_and_prompt_in_view() {
+        let directory = tempfile::tempdir().unwrap();
+        let keymap = Keymap::default();
+        let totps = (0..20)
+            .map(|x| Totp::new(&format!("Entry {x:02}"), "MFRGGZDF", 6, 30))
+            .collect();
+        let elements: Vec<Box<dyn Element>> = vec![
+            Box::new(TotpLineParagraph::new()),
+            Box::new(TotpListView::new(
+                at(0),
+                directory.path().join("totp.txt"),
+                6,
+                30,
+                totps,
+                Box::new(|_: &Totp| {}),
+                State::load(directory.path().join("state.txt")).unwrap(),
+            )),
+        ];
+        let mut app = App::new(elements, &keymap);
+
+        for event in [
+            key(KeyCode::Char('G')),
+            key(KeyCode::Char('/')),
+            key(KeyCode::Char('1')),
+            key(KeyCode::Char('9')),
+        ] {
+            app.handle_event(&event, at(0));
+        }
+        let buffer = app.render(at(5), WIDTH, HEIGHT);
+
+        assert_eq!(
+            vec![
+                "05/30",
+                "Entry 16 | 711370",
+                "Entry 17 | 711370",
+                "Entry 18 | 711370",
+                "Entry 19 | 711370",
+                "",
+                "/19",
+                ""
+            ],
+            buffer.lines()
+        );
+        assert_eq!(Theme::default().selected, buffer.cell(0, 4).unwrap().style);
+
+        // Moving up past the top scrolls back.
+        app.handle_event(&key(KeyCode::Enter), at(5));
+        app.handle_event(&key(KeyCode::Char('g')), at(5));
+        app.handle_event(&key(KeyCode::Char('g')), at(5));
+        let buffer = app.render(at(5), WIDTH, HEIGHT);
+        assert_eq!("Entry 00 | 711370", buffer.lines()[1]);
+        assert_eq!("Entry 03 | 711370", buffer.lines()[4]);
+    }
 }
@@ -0,0 +1,116 @@
+use std::{
+    io::{self, Write},
+    panic, process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    thread,
+};
+
+use crossterm::{
+    cursor,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute, style, terminal,
+};
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM},
+    iterator::{Handle, Signals},
+};
+
+// Whether the terminal is set up for the TUI, it is only restored when it is.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Sets the terminal up for the TUI and restores it when dropped,
+/// so the terminal is restored on every way out of the TUI, including errors and panics.
+pub struct TerminalGuard<'a, W: Write> {
+    w: &'a mut W,
+}
+
+impl<'a, W: Write> TerminalGuard<'a, W> {
+    pub fn enter(w: &'a mut W) -> io::Result<Self> {
+        ACTIVE.store(true, Ordering::SeqCst);
+        // The guard is created first, so the terminal is restored when setting it up fails halfway.
+        let guard = Self { w };
+        execute!(
+            guard.w,
+            terminal::EnterAlternateScreen,
+            EnableMouseCapture,
+            cursor::Hide
+        )?;
+        terminal::enable_raw_mode()?;
+
+        Ok(guard)
+    }
+
+    pub fn writer(&mut self) -> &mut W {
+        self.w
+    }
+}
+
+impl<W: Write> Drop for TerminalGuard<'_, W> {
+    fn drop(&mut self) {
+        restore(self.w);
+    }
+}
+
+// Errors are ignored, there is nothing left to do when the terminal can not be restored.
+fn restore<W: Write>(w: &mut W) {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let _ = execute!(
+            w,
+            style::ResetColor,
+            DisableMouseCapture,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Restores the terminal before the panic message is printed, it would be lost on the alternate screen otherwise.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore(&mut io::stdout());
+            previous_hook(info);
+        }));
+    });
+}
+
+/// Restores the terminal and exits when the process is interrupted, terminated or its terminal is closed.
+/// The signals are handled until the returned handle is closed.
+pub fn handle_signals() -> io::Result<Handle> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    let handle = signals.handle();
+
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            restore(&mut io::stdout());
+            process::exit(128 + signal);
+        }
+    });
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminal_is_restored_when_the_guard_is_dropped() {
+        let mut output = vec![];
+
+        // Raw mode can not be enabled without a terminal, the guard restores what was set up before that.
+        drop(TerminalGuard::enter(&mut output));
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("\u{1b}[?1049h"));
+        assert!(output.ends_with("\u{1b}[?1049l"));
+        assert!(!ACTIVE.load(Ordering::SeqCst));
+    }
+}
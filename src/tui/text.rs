@@ -0,0 +1,86 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The number of terminal cells the grapheme takes, wide characters like CJK and emoji take two cells
+/// and combining characters are part of the grapheme before them.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().min(2)
+}
+
+/// The number of terminal cells the text takes.
+pub fn width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Pads the text with spaces at the end to the width.
+pub fn pad(text: &str, width: usize) -> String {
+    format!(
+        "{text}{}",
+        " ".repeat(width.saturating_sub(self::width(text)))
+    )
+}
+
+/// Cuts off the text to the width, text that does not fit ends with an ellipsis.
+/// Wide characters are never split, so the text can be a cell narrower than the width.
+pub fn truncate(text: &str, width: usize) -> String {
+    if self::width(text) <= width {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut truncated_width = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme_width(grapheme);
+        // A cell is kept for the ellipsis.
+        if truncated_width + grapheme_width + 1 > width {
+            break;
+        }
+        truncated.push_str(grapheme);
+        truncated_width += grapheme_width;
+    }
+
+    if width > 0 {
+        truncated.push('…');
+    }
+
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 'e' followed by a combining acute accent.
+    const COMBINING: &str = "Cafe\u{301}";
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        assert_eq!(9, width("Acme Inc."));
+        assert_eq!(8, width("日本銀行"));
+        assert_eq!(4, width(COMBINING));
+        assert_eq!(2, width("👍"));
+        // Emoji joined into a single grapheme.
+        assert_eq!(2, width("👨‍👩‍👧"));
+    }
+
+    #[test]
+    fn padding_uses_the_width() {
+        assert_eq!("日本  ", pad("日本", 6));
+        assert_eq!(format!("{COMBINING}  "), pad(COMBINING, 6));
+        assert_eq!("Acme Inc.", pad("Acme Inc.", 4));
+    }
+
+    #[test]
+    fn truncation_does_not_split_graphemes() {
+        assert_eq!("日本銀行", truncate("日本銀行", 8));
+        assert_eq!("日本銀…", truncate("日本銀行", 7));
+        // The wide character does not fit next to the ellipsis.
+        assert_eq!("日本…", truncate("日本銀行", 6));
+        assert_eq!("Caf…", truncate(&format!("{COMBINING}s"), 4));
+        assert_eq!(
+            format!("{COMBINING}…"),
+            truncate(&format!("{COMBINING}s!"), 5)
+        );
+        assert_eq!("", truncate("日本", 0));
+    }
+}
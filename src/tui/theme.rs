@@ -0,0 +1,291 @@
+use std::{env, error::Error, ffi::OsStr, fmt};
+
+use crossterm::style::{self, Attribute, Attributes, Color, ContentStyle};
+
+use crate::settings::Settings;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum ThemeError {
+    InvalidTheme(String),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ThemeError::InvalidTheme(message) => message,
+            }
+        )
+    }
+}
+
+impl Error for ThemeError {}
+
+const THEME_NAMES: [&str; 4] = ["default", "light", "high_contrast", "monochrome"];
+
+/// The styles used by the TUI, a built-in theme is chosen with the `theme` setting
+/// and every style can be changed with `theme.<style>`, for example `theme.selected = bold yellow on blue`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Theme {
+    pub selected: ContentStyle,
+    pub marked: ContentStyle,
+    pub name: ContentStyle,
+    pub code: ContentStyle,
+    pub countdown: ContentStyle,
+    pub countdown_warning: ContentStyle,
+    pub countdown_critical: ContentStyle,
+    pub search_prompt: ContentStyle,
+    pub error: ContentStyle,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            selected: foreground(Color::Blue),
+            marked: foreground(Color::Blue),
+            name: ContentStyle::new(),
+            code: ContentStyle::new(),
+            countdown: foreground(Color::Grey),
+            countdown_warning: foreground(Color::Yellow),
+            countdown_critical: foreground(Color::Red),
+            search_prompt: ContentStyle::new(),
+            error: foreground(Color::Red),
+        }
+    }
+}
+
+fn foreground(color: Color) -> ContentStyle {
+    ContentStyle {
+        foreground_color: Some(color),
+        ..ContentStyle::new()
+    }
+}
+
+fn with_attributes(mut style: ContentStyle, attributes: &[Attribute]) -> ContentStyle {
+    style.attributes.extend(Attributes::from(attributes));
+    style
+}
+
+/// Parses a style like `bold yellow on blue`, colors are named like `dark_red` or given as `#rrggbb`.
+fn parse_style(value: &str) -> Result<ContentStyle, String> {
+    let mut style = ContentStyle::new();
+    let mut words = value.split_whitespace();
+
+    while let Some(word) = words.next() {
+        match word {
+            "none" => {}
+            "bold" => style.attributes.set(Attribute::Bold),
+            "dim" => style.attributes.set(Attribute::Dim),
+            "italic" => style.attributes.set(Attribute::Italic),
+            "underlined" => style.attributes.set(Attribute::Underlined),
+            "reverse" => style.attributes.set(Attribute::Reverse),
+            "on" => {
+                let color = words
+                    .next()
+                    .ok_or_else(|| "Expected a color after 'on'.".to_string())?;
+                style.background_color = Some(parse_color(color)?);
+            }
+            _ => style.foreground_color = Some(parse_color(word)?),
+        }
+    }
+
+    Ok(style)
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    let rgb = value
+        .strip_prefix('#')
+        .filter(|x| x.len() == 6)
+        .and_then(|x| u32::from_str_radix(x, 16).ok());
+
+    match rgb {
+        Some(rgb) => Ok(Color::Rgb {
+            r: (rgb >> 16 & 0xff) as u8,
+            g: (rgb >> 8 & 0xff) as u8,
+            b: (rgb & 0xff) as u8,
+        }),
+        None => Color::try_from(value).map_err(|()| format!("Unknown color or style '{value}'.")),
+    }
+}
+
+impl Theme {
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "light" => Some(Self {
+                selected: foreground(Color::DarkBlue),
+                marked: foreground(Color::DarkBlue),
+                code: foreground(Color::DarkGreen),
+                countdown: foreground(Color::DarkGrey),
+                countdown_warning: foreground(Color::DarkYellow),
+                countdown_critical: foreground(Color::DarkRed),
+                error: foreground(Color::DarkRed),
+                ..Self::default()
+            }),
+            "high_contrast" => Some(Self {
+                selected: with_attributes(
+                    ContentStyle {
+                        foreground_color: Some(Color::Black),
+                        background_color: Some(Color::Yellow),
+                        ..ContentStyle::new()
+                    },
+                    &[Attribute::Bold],
+                ),
+                marked: with_attributes(foreground(Color::Yellow), &[Attribute::Bold]),
+                code: with_attributes(foreground(Color::White), &[Attribute::Bold]),
+                countdown: foreground(Color::White),
+                countdown_warning: with_attributes(foreground(Color::Yellow), &[Attribute::Bold]),
+                countdown_critical: with_attributes(foreground(Color::Red), &[Attribute::Bold]),
+                search_prompt: with_attributes(foreground(Color::White), &[Attribute::Bold]),
+                error: with_attributes(foreground(Color::Red), &[Attribute::Bold]),
+                ..Self::default()
+            }),
+            "monochrome" => Some(Self::monochrome()),
+            _ => None,
+        }
+    }
+
+    /// Used when colors are not available, the selection is shown in reverse video instead.
+    pub fn monochrome() -> Self {
+        let bold = with_attributes(ContentStyle::new(), &[Attribute::Bold]);
+
+        Self {
+            selected: with_attributes(ContentStyle::new(), &[Attribute::Reverse]),
+            marked: bold,
+            name: ContentStyle::new(),
+            code: ContentStyle::new(),
+            countdown: ContentStyle::new(),
+            countdown_warning: bold,
+            countdown_critical: with_attributes(ContentStyle::new(), &[Attribute::Reverse]),
+            search_prompt: ContentStyle::new(),
+            error: bold,
+        }
+    }
+
+    fn styles_mut(&mut self) -> [(&str, &mut ContentStyle); 9] {
+        [
+            ("selected", &mut self.selected),
+            ("marked", &mut self.marked),
+            ("name", &mut self.name),
+            ("code", &mut self.code),
+            ("countdown", &mut self.countdown),
+            ("countdown_warning", &mut self.countdown_warning),
+            ("countdown_critical", &mut self.countdown_critical),
+            ("search_prompt", &mut self.search_prompt),
+            ("error", &mut self.error),
+        ]
+    }
+
+    /// The theme of the `theme` setting with the styles changed by the `theme.<style>` settings.
+    /// When `monochrome` is set the settings are ignored and the monochrome theme is used.
+    pub fn from_settings(settings: &Settings, monochrome: bool) -> Result<Self, ThemeError> {
+        if monochrome {
+            return Ok(Self::monochrome());
+        }
+
+        let mut theme = match settings.get("theme") {
+            Some(name) => Self::named(name).ok_or_else(|| {
+                ThemeError::InvalidTheme(format!(
+                    "Unknown theme '{name}', the themes are {}.",
+                    THEME_NAMES.join(", ")
+                ))
+            })?,
+            None => Self::default(),
+        };
+
+        for (name, style) in theme.styles_mut() {
+            if let Some(value) = settings.get(&format!("theme.{name}")) {
+                *style = parse_style(value).map_err(|err| {
+                    ThemeError::InvalidTheme(format!(
+                        "Invalid value for setting 'theme.{name}': {err}"
+                    ))
+                })?;
+            }
+        }
+
+        Ok(theme)
+    }
+}
+
+// The fewest colors a terminal needs to show the themes, the basic ANSI colors.
+const MIN_COLOR_COUNT: u16 = 8;
+
+// Colors are disabled with a non-empty NO_COLOR, see https://no-color.org,
+// and on terminals without colors, which are dumb terminals, terminals without a TERM
+// and terminals that support fewer colors than the themes use.
+fn is_monochrome(no_color: Option<&OsStr>, term: Option<&str>, color_count: u16) -> bool {
+    no_color.is_some_and(|x| !x.is_empty())
+        || matches!(term, None | Some("" | "dumb"))
+        || color_count < MIN_COLOR_COUNT
+}
+
+/// Whether the TUI should be drawn without colors.
+pub fn is_monochrome_terminal() -> bool {
+    is_monochrome(
+        env::var_os("NO_COLOR").as_deref(),
+        env::var("TERM").ok().as_deref(),
+        style::available_color_count(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_styles() {
+        assert_eq!(
+            Ok(with_attributes(
+                ContentStyle {
+                    foreground_color: Some(Color::Yellow),
+                    background_color: Some(Color::Rgb {
+                        r: 0x12,
+                        g: 0x34,
+                        b: 0xab
+                    }),
+                    ..ContentStyle::new()
+                },
+                &[Attribute::Bold]
+            )),
+            parse_style("bold yellow on #1234ab")
+        );
+        assert_eq!(Ok(ContentStyle::new()), parse_style("none"));
+        assert!(parse_style("yellow on").is_err());
+        assert!(parse_style("blinking").is_err());
+    }
+
+    #[test]
+    fn settings_choose_theme_and_change_styles() {
+        let settings = Settings::parse("theme = light\ntheme.error = bold magenta").unwrap();
+        let theme = Theme::from_settings(&settings, false).unwrap();
+
+        assert_eq!(Theme::named("light").unwrap().selected, theme.selected);
+        assert_eq!(
+            with_attributes(foreground(Color::Magenta), &[Attribute::Bold]),
+            theme.error
+        );
+
+        // Without colors the monochrome theme is used, whatever the settings are.
+        assert_eq!(
+            Ok(Theme::monochrome()),
+            Theme::from_settings(&settings, true)
+        );
+        assert!(matches!(
+            Theme::from_settings(&Settings::parse("theme = neon").unwrap(), false),
+            Err(ThemeError::InvalidTheme(message)) if message.contains("neon")
+        ));
+    }
+
+    #[test]
+    fn no_color_and_dumb_terminals_are_monochrome() {
+        assert!(is_monochrome(Some(OsStr::new("1")), Some("xterm"), 8));
+        assert!(is_monochrome(None, Some("dumb"), 8));
+        assert!(is_monochrome(None, None, 8));
+        assert!(is_monochrome(None, Some(""), 8));
+        assert!(is_monochrome(None, Some("vt100"), 0));
+        assert!(!is_monochrome(Some(OsStr::new("")), Some("xterm"), 8));
+        assert!(!is_monochrome(None, Some("xterm-256color"), 256));
+    }
+}
@@ -1,66 +1,76 @@
-use std::{error::Error, io::Stdout, time::SystemTime};
-
-use crossterm::{
-    cursor, queue,
-    style::{self, Stylize},
-};
+use std::time::SystemTime;
 
 use crate::totp;
 
-use super::{widgets::LineParagraph, Display, Element, HandleEvent, Refresh};
+use super::{
+    widgets::LineParagraph, Buffer, CountedAction, Display, Element, HandleEvent, Lock, Rect,
+    Refresh, Theme,
+};
+
+// The countdown is shown as a warning, and as critical, when this few seconds remain.
+const WARNING_SECONDS: u64 = 10;
+const CRITICAL_SECONDS: u64 = 5;
 
 pub struct TotpLineParagraph {
     line_paragraph: LineParagraph,
-    redraw: bool,
+    theme: Theme,
+    seconds_remaining: u64,
 }
 
 impl TotpLineParagraph {
     pub fn new() -> Self {
         Self {
             line_paragraph: LineParagraph::new(String::new()),
-            // It's important to set the redraw to true first time we run,
-            // otherwise it will first be redrawn on next change.
-            redraw: true,
+            theme: Theme::default(),
+            seconds_remaining: 0,
         }
     }
 
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
     fn update_line_text(&mut self, duration_max: u64, time: SystemTime) {
         let duration_used = totp::duration_used(duration_max, time);
-        let new_paragraph_text = format!("{duration_used:02}/{duration_max}");
-
-        // No reason to redraw if the text has not changed.
-        if new_paragraph_text != self.line_paragraph.text {
-            self.line_paragraph.text = new_paragraph_text;
-            self.redraw = true;
-        }
+        self.line_paragraph.text = format!("{duration_used:02}/{duration_max}");
+        self.seconds_remaining = duration_max.saturating_sub(duration_used);
     }
 }
 
 impl Element for TotpLineParagraph {}
 
-impl Display for TotpLineParagraph {
-    fn display(&mut self, w: &mut Stdout) -> Result<(), Box<dyn Error>> {
-        if self.redraw {
-            queue!(
-                w,
-                style::PrintStyledContent(self.line_paragraph.text.clone().grey()),
-            )?;
-
-            self.redraw = false;
-        }
+impl Lock for TotpLineParagraph {}
 
-        queue!(w, cursor::MoveToNextLine(1))?;
+impl Display for TotpLineParagraph {
+    fn display(&mut self, buffer: &mut Buffer, area: Rect) {
+        buffer.move_to(area.x, area.y);
+        let style = if self.seconds_remaining <= CRITICAL_SECONDS {
+            self.theme.countdown_critical
+        } else if self.seconds_remaining <= WARNING_SECONDS {
+            self.theme.countdown_warning
+        } else {
+            self.theme.countdown
+        };
+        buffer.print_styled(&self.line_paragraph.text, style);
+    }
 
-        Ok(())
+    fn height(&self) -> Option<u16> {
+        Some(1)
     }
 }
 
 impl Refresh for TotpLineParagraph {
-    fn refresh(&mut self) {
-        self.update_line_text(30, SystemTime::now());
+    fn refresh(&mut self, time: SystemTime) {
+        self.update_line_text(30, time);
+    }
+
+    // The countdown changes every second.
+    fn next_refresh(&self, time: SystemTime) -> Option<SystemTime> {
+        Some(totp::next_rollover(1, time))
     }
 }
 
 impl HandleEvent for TotpLineParagraph {
-    fn handle_event(&mut self, _: &crossterm::event::Event) {}
+    fn handle_event(&mut self, _: &crossterm::event::Event, _: Option<CountedAction>) {}
 }
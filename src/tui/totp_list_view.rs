@@ -1,24 +1,56 @@
-use arboard::Clipboard;
-use crossterm::event::KeyEvent;
-use crossterm::style::Stylize;
-use crossterm::terminal::{Clear, ClearType};
-use crossterm::{
-    cursor,
-    event::{Event, KeyCode},
-    queue, style,
-};
+use crossterm::event::{Event, KeyCode};
+use crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use crossterm::style::{Attribute, ContentStyle};
 use std::error::Error;
-use std::io::Stdout;
-use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use zeroize::Zeroizing;
 
+use crate::config;
+use crate::frecency;
+use crate::state::State;
 use crate::totp::{self, Totp};
 
+use super::text;
 use super::widgets::{LineItem, ListView};
-use super::{Display, Element, HandleEvent, Refresh};
+use super::{
+    Action, Buffer, CountedAction, Display, Element, HandleEvent, Lock, Rect, Refresh, Theme,
+};
+
+// The number of most used entries that are highlighted.
+const FREQUENT_COUNT: usize = 5;
+const SORT_STATE_KEY: &str = "sort";
+const PIN_STATE_PREFIX: &str = "pin";
+// Used until the list is drawn for the first time.
+const DEFAULT_PAGE_SIZE: usize = 10;
+// Two clicks on the same row within this interval are a double click.
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+// How long a masked code is shown after revealing it.
+const REVEAL_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum SortMode {
+    // The order of the secrets file.
+    File,
+    Frecency,
+}
+
+// A line of the list, either the header of a group or the index of a TOTP in the secrets file.
+// Rows only hold the index, so the secrets are not copied into every line.
+#[derive(PartialEq, Debug, Clone)]
+enum Row {
+    Header(String),
+    Entry(usize),
+}
 
 enum TotpCommandType {
     Search,
+    // Add takes either an otpauth URI or the name of the new entry,
+    // if a name is given the secret is entered afterwards.
+    Add,
+    AddSecret(String),
+    Rename(String),
+    Delete(String),
 }
 
 struct TotpCommand {
@@ -26,6 +58,25 @@ struct TotpCommand {
     input: String,
 }
 
+// Masks the secret of a pasted URI string, the same as a typed secret is masked.
+fn mask_uri_secret(input: &str) -> String {
+    let Some(start) = input
+        .to_ascii_lowercase()
+        .find("secret=")
+        .map(|x| x + "secret=".len())
+    else {
+        return input.to_string();
+    };
+    let end = input[start..].find('&').map_or(input.len(), |x| start + x);
+
+    format!(
+        "{}{}{}",
+        &input[..start],
+        "*".repeat(input[start..end].chars().count()),
+        &input[end..]
+    )
+}
+
 impl TotpCommand {
     fn new(command_type: TotpCommandType) -> Self {
         Self {
@@ -33,53 +84,357 @@ impl TotpCommand {
             input: String::new(),
         }
     }
+
+    fn prompt(&self) -> String {
+        match &self.command_type {
+            TotpCommandType::Search => format!("/{}", self.input),
+            TotpCommandType::Add => {
+                format!(
+                    "Add (otpauth URI or name): {}",
+                    mask_uri_secret(&self.input)
+                )
+            }
+            // The secret is masked, so it is not leaked when sharing the screen.
+            TotpCommandType::AddSecret(name) => format!(
+                "Secret for '{name}': {}",
+                "*".repeat(self.input.chars().count())
+            ),
+            TotpCommandType::Rename(name) => format!("Rename '{name}' to: {}", self.input),
+            TotpCommandType::Delete(name) => format!("Delete '{name}'? (y/n)"),
+        }
+    }
 }
 
 pub struct TotpListView {
     totps: Vec<Totp>,
-    list_view: ListView<Totp>,
+    list_view: ListView<Row>,
+    // Called with the TOTP of the selected line, to copy its code.
+    copy_code: Box<dyn Fn(&Totp)>,
+    // The pinned TOTPs are listed first, in a section of their own.
+    pinned_count: usize,
+    // The indices of the most used TOTPs, they are highlighted in the list.
+    frequent: Vec<usize>,
+    sort_mode: SortMode,
+    collapsed_groups: Vec<String>,
+    // Only TOTPs with a tag starting with the filter are listed.
+    tag_filter: Option<String>,
+    state: State,
+    config_file_path: PathBuf,
+    digits: u32,
     interval: u64,
-    // Refreshed flag is there to avoid multiple refreshes in a row when duration is 0.
-    refreshed: bool,
+    // The time of the last refresh, the codes are shown for this time.
+    time: SystemTime,
+    // The counter of every TOTP when the line items were created, they are created again
+    // when a counter changes.
+    counters: Vec<u64>,
     command: Option<TotpCommand>,
+    // The last submitted search, repeated with the next and previous match actions.
+    last_search: Option<String>,
+    // Message shown to the user until the next key press, for example when saving fails.
+    message: Option<String>,
+    // The column and row each line item was last drawn at, relative to the top left of the view,
+    // `None` for line items that are scrolled out of view.
+    line_item_positions: Vec<Option<Position>>,
+    column_width: u16,
+    // The first row of the list that is shown, the list scrolls to keep the selected line in view.
+    scroll_offset: u16,
+    // The number of rows of the list when it was last drawn.
+    page_size: usize,
+    // The index of the clicked entry and the time of the last click, to detect double clicks.
+    last_click: Option<(usize, Instant)>,
+    theme: Theme,
+    // The codes are masked, so they are not leaked when sharing the screen.
+    private: bool,
+    // The index of the TOTP whose code is shown while the codes are masked, and until when.
+    revealed: Option<(usize, SystemTime)>,
 }
 
-fn format_totp(config: &Totp, time: SystemTime, name_max_length: usize) -> String {
+fn format_totp(config: &Totp, time: SystemTime, name_max_length: usize, masked: bool) -> String {
     format!(
-        "{:<max_length$} | {}",
-        config.name,
-        config.code_padded(time),
-        max_length = name_max_length
+        "{} | {}",
+        text::pad(&config.name, name_max_length),
+        shown_code(config, time, masked)
     )
 }
 
+// Masked codes are split in two halves, like '••• •••'.
+fn shown_code(config: &Totp, time: SystemTime, masked: bool) -> String {
+    if masked {
+        let first_half = config.digits as usize / 2;
+        format!(
+            "{} {}",
+            "•".repeat(first_half),
+            "•".repeat(config.digits as usize - first_half)
+        )
+    } else {
+        config.code_padded(time)
+    }
+}
+
+fn format_header(group: &str, collapsed: bool) -> String {
+    format!("{} {group}", if collapsed { '▸' } else { '▾' })
+}
+
 fn longest_string(strs: &[&str]) -> Option<usize> {
-    strs.iter()
-        .max_by(|x, y| x.chars().count().cmp(&y.chars().count()))
-        .map(|config| config.chars().count())
+    strs.iter().map(|x| text::width(x)).max()
+}
+
+/// Creates the rows from the TOTPs in the given order, the pinned TOTPs come first,
+/// then the TOTPs without a group and then every group under its header.
+/// Groups are listed in the order they appear in the secrets file. Returns the rows
+/// and the number of pinned rows.
+fn create_rows(
+    totps: &[Totp],
+    order: &[usize],
+    is_pinned: impl Fn(usize) -> bool,
+    collapsed_groups: &[String],
+) -> (Vec<Row>, usize) {
+    let entry = |index: &usize| Row::Entry(*index);
+
+    let (pinned, rest): (Vec<usize>, Vec<usize>) =
+        order.iter().partition(|index| is_pinned(**index));
+    let mut rows = pinned.iter().map(entry).collect::<Vec<_>>();
+
+    let mut groups: Vec<Option<&str>> = vec![None];
+    for totp in totps {
+        if !groups.contains(&totp.group.as_deref()) {
+            groups.push(totp.group.as_deref());
+        }
+    }
+
+    for group in groups {
+        let entries = rest
+            .iter()
+            .filter(|index| totps[**index].group.as_deref() == group)
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            continue;
+        }
+
+        if let Some(group) = group {
+            rows.push(Row::Header(group.to_string()));
+            if collapsed_groups.iter().any(|x| x == group) {
+                continue;
+            }
+        }
+        rows.extend(entries.into_iter().map(entry));
+    }
+
+    (rows, pinned.len())
 }
 
-fn create_line_items(totps: &[Totp], time: SystemTime) -> Vec<LineItem<Totp>> {
+fn create_line_items(
+    totps: &[Totp],
+    rows: Vec<Row>,
+    collapsed_groups: &[String],
+    time: SystemTime,
+    masked: bool,
+) -> Vec<LineItem<Row>> {
     let names: Vec<&str> = totps.iter().map(|x| x.name.as_str()).collect();
     let max_name_length = longest_string(&names).expect("Could not get longest name.");
 
-    totps
-        .iter()
-        .map(|config| LineItem::new(&format_totp(config, time, max_name_length), config.clone()))
+    rows.into_iter()
+        .map(|row| {
+            let text = match &row {
+                Row::Header(group) => {
+                    format_header(group, collapsed_groups.iter().any(|x| x == group))
+                }
+                Row::Entry(index) => format_totp(&totps[*index], time, max_name_length, masked),
+            };
+            LineItem::new(&text, row)
+        })
         .collect()
 }
 
+// The code is shown before the name on terminals narrower than this,
+// terminals at least this wide show the list in a grid of columns.
+const COMPACT_WIDTH: usize = 30;
+const GRID_WIDTH: usize = 120;
+const COLUMN_GAP: usize = 4;
+const SEPARATOR: &str = " | ";
+// The ' *' after a marked line.
+const MARK_WIDTH: usize = 2;
+// The empty row, the prompt and the message below the list.
+const STATUS_ROWS: u16 = 3;
+
+// A column and row in the list.
+type Position = (u16, u16);
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct ListLayout {
+    // The code is shown before the name, without the separator.
+    compact: bool,
+    name_width: usize,
+    column_width: usize,
+    // The line items continue in the next column after this many rows.
+    rows_per_column: u16,
+}
+
+/// Fits the list into the width, names that are too long are cut off.
+fn list_layout(width: u16, longest_name: usize, code_width: usize, list_rows: u16) -> ListLayout {
+    let width = usize::from(width);
+    let line_width = longest_name + SEPARATOR.len() + code_width + MARK_WIDTH;
+
+    if width < COMPACT_WIDTH {
+        ListLayout {
+            compact: true,
+            name_width: width.saturating_sub(code_width + 1 + MARK_WIDTH),
+            column_width: width,
+            rows_per_column: u16::MAX,
+        }
+    } else if width >= GRID_WIDTH && width >= 2 * (line_width + COLUMN_GAP) {
+        ListLayout {
+            compact: false,
+            name_width: longest_name,
+            column_width: line_width + COLUMN_GAP,
+            rows_per_column: list_rows.max(1),
+        }
+    } else {
+        ListLayout {
+            compact: false,
+            name_width: longest_name
+                .min(width.saturating_sub(SEPARATOR.len() + code_width + MARK_WIDTH)),
+            column_width: width,
+            rows_per_column: u16::MAX,
+        }
+    }
+}
+
+/// The column and row of each line item and of the divider after the pinned section,
+/// the divider takes a row of its own.
+fn line_item_positions(
+    line_item_count: usize,
+    pinned_count: usize,
+    rows_per_column: u16,
+) -> (Vec<Position>, Option<Position>) {
+    let has_divider = pinned_count > 0 && pinned_count < line_item_count;
+    let slot_count = line_item_count + usize::from(has_divider);
+    let rows_per_column = usize::from(rows_per_column.max(1));
+    let mut positions = (0..slot_count).map(|slot| {
+        (
+            u16::try_from(slot / rows_per_column).unwrap_or(u16::MAX),
+            u16::try_from(slot % rows_per_column).unwrap_or(u16::MAX),
+        )
+    });
+
+    let mut line_item_positions = positions.by_ref().take(pinned_count).collect::<Vec<_>>();
+    let divider_position = if has_divider { positions.next() } else { None };
+    line_item_positions.extend(positions);
+
+    (line_item_positions, divider_position)
+}
+
+/// The first row to show of a list with `row_count` rows, so that the row is in view.
+/// The list only scrolls as far as needed, and never further than its last row.
+fn scroll_offset(offset: u16, row: u16, row_count: u16, list_rows: u16) -> u16 {
+    offset
+        .min(row_count.saturating_sub(list_rows))
+        .min(row)
+        .max(row.saturating_add(1).saturating_sub(list_rows))
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Part {
+    Name,
+    Code,
+    Text,
+}
+
+// The parts of a line, fitted into the layout.
+fn line_parts(
+    line: &LineItem<Row>,
+    totps: &[Totp],
+    time: SystemTime,
+    layout: &ListLayout,
+    masked: bool,
+) -> Vec<(String, Part)> {
+    let totp = match line.value() {
+        Row::Header(_) => {
+            return vec![(
+                text::truncate(line.text(), layout.column_width.saturating_sub(MARK_WIDTH)),
+                Part::Text,
+            )]
+        }
+        Row::Entry(index) => &totps[*index],
+    };
+
+    if layout.compact {
+        vec![
+            (shown_code(totp, time, masked), Part::Code),
+            (" ".to_string(), Part::Text),
+            (text::truncate(&totp.name, layout.name_width), Part::Name),
+        ]
+    } else {
+        vec![
+            (
+                text::pad(
+                    &text::truncate(&totp.name, layout.name_width),
+                    layout.name_width,
+                ),
+                Part::Name,
+            ),
+            (SEPARATOR.to_string(), Part::Text),
+            (shown_code(totp, time, masked), Part::Code),
+        ]
+    }
+}
+
+/// The parts of a line with their style, the name and code of an entry are styled separately
+/// unless the line is selected. Headers are underlined and the most used entries are bold in every theme.
+fn styled_line_parts(
+    parts: Vec<(String, Part)>,
+    row: &Row,
+    is_selected: bool,
+    is_frequent: bool,
+    theme: &Theme,
+) -> Vec<(String, ContentStyle)> {
+    let emphasis = match row {
+        Row::Header(_) => Some(Attribute::Underlined),
+        Row::Entry(..) if is_frequent => Some(Attribute::Bold),
+        Row::Entry(..) => None,
+    };
+
+    parts
+        .into_iter()
+        .map(|(text, part)| {
+            let mut style = match part {
+                _ if is_selected => theme.selected,
+                Part::Name => theme.name,
+                Part::Code => theme.code,
+                Part::Text => ContentStyle::new(),
+            };
+            if let Some(attribute) = emphasis {
+                style.attributes.set(attribute);
+            }
+            (text, style)
+        })
+        .collect()
+}
+
+fn counters(totps: &[Totp], time: SystemTime) -> Vec<u64> {
+    totps.iter().map(|totp| totp.counter(time)).collect()
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn matches_search(line_item: &LineItem<Row>, search_text: &str) -> bool {
+    line_item
+        .text()
+        .to_lowercase()
+        .starts_with(&search_text.to_lowercase())
+}
+
 fn find_line_item_matching_search(
-    line_items: &[LineItem<Totp>],
+    line_items: &[LineItem<Row>],
     search_text: &str,
 ) -> Option<usize> {
     let mut found_index: Option<usize> = None;
     for (index, line_item) in line_items.iter().enumerate() {
-        if line_item
-            .text()
-            .to_lowercase()
-            .starts_with(&search_text.to_lowercase())
-        {
+        if matches_search(line_item, search_text) {
             found_index = Some(index);
         }
     }
@@ -90,27 +445,424 @@ fn find_line_item_matching_search(
 impl TotpListView {
     pub fn new(
         time: SystemTime,
+        config_file_path: PathBuf,
+        digits: u32,
         interval: u64,
         totps: Vec<Totp>,
-        clipboard: Arc<Mutex<Clipboard>>,
+        copy_code: Box<dyn Fn(&Totp)>,
+        state: State,
     ) -> Self {
-        let line_items = create_line_items(&totps, time);
-        Self {
+        let sort_mode = match state.get(SORT_STATE_KEY) {
+            Some("frecency") => SortMode::Frecency,
+            _ => SortMode::File,
+        };
+
+        let mut totp_list_view = Self {
             totps,
-            list_view: ListView::new(
-                line_items,
-                Box::new(move |totp| {
-                    let time = SystemTime::now();
-                    clipboard
-                        .lock()
-                        .expect("Could not lock clipboard.")
-                        .set_text(totp.code_padded(time))
-                        .expect("Could not set text in clipboard.");
-                }),
-            ),
+            pinned_count: 0,
+            frequent: vec![],
+            sort_mode,
+            collapsed_groups: vec![],
+            tag_filter: None,
+            state,
+            list_view: ListView::new(vec![]),
+            copy_code,
+            config_file_path,
+            digits,
             interval,
-            refreshed: false,
+            time,
+            counters: vec![],
             command: None,
+            last_search: None,
+            message: None,
+            line_item_positions: vec![],
+            column_width: 0,
+            scroll_offset: 0,
+            page_size: DEFAULT_PAGE_SIZE,
+            last_click: None,
+            theme: Theme::default(),
+            private: false,
+            revealed: None,
+        };
+        totp_list_view.update_line_items(time);
+
+        totp_list_view
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_privacy(mut self, private: bool) -> Self {
+        self.private = private;
+        self.update_line_items(self.time);
+        self
+    }
+
+    fn half_page_size(&self) -> usize {
+        (self.page_size / 2).max(1)
+    }
+
+    fn selected_row(&self) -> Option<&Row> {
+        self.list_view.get_selected_line_item().map(LineItem::value)
+    }
+
+    // The index of the selected TOTP in the secrets file, `None` when a header is selected.
+    fn selected_index(&self) -> Option<usize> {
+        match self.selected_row() {
+            Some(Row::Entry(index)) => Some(*index),
+            _ => None,
+        }
+    }
+
+    fn selected_name(&self) -> Option<String> {
+        self.selected_index()
+            .map(|index| self.totps[index].name.clone())
+    }
+
+    // Creates the line items from the TOTPs, the selection stays on the same row.
+    fn update_line_items(&mut self, time: SystemTime) {
+        let selected_row = self.selected_row().cloned();
+        self.counters = counters(&self.totps, time);
+        let by_frecency = frecency::sort_by_frecency(&self.state, &self.totps, unix_seconds(time));
+
+        self.frequent = by_frecency
+            .iter()
+            .copied()
+            .filter(|index| frecency::usage(&self.state, &self.totps[*index]).count > 0)
+            .take(FREQUENT_COUNT)
+            .collect();
+        let mut order = match self.sort_mode {
+            SortMode::File => (0..self.totps.len()).collect(),
+            SortMode::Frecency => by_frecency,
+        };
+        if let Some(tag_filter) = &self.tag_filter {
+            order.retain(|index| self.totps[*index].has_tag_starting_with(tag_filter));
+        }
+
+        let (rows, pinned_count) = create_rows(
+            &self.totps,
+            &order,
+            |index| self.is_pinned(index),
+            &self.collapsed_groups,
+        );
+        self.pinned_count = pinned_count;
+
+        self.list_view.set_line_items(create_line_items(
+            &self.totps,
+            rows,
+            &self.collapsed_groups,
+            time,
+            self.private,
+        ));
+        if let Some(position) = self
+            .list_view
+            .line_items
+            .iter()
+            .position(|x| Some(x.value()) == selected_row.as_ref())
+        {
+            self.list_view.current_index = position;
+        }
+    }
+
+    fn is_pinned(&self, index: usize) -> bool {
+        self.state
+            .get(&self.state.entry_key(PIN_STATE_PREFIX, &self.totps[index]))
+            .is_some()
+    }
+
+    // Pins are kept in the state file, so the secrets file is not reordered.
+    fn toggle_pin(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+
+        let key = self.state.entry_key(PIN_STATE_PREFIX, &self.totps[index]);
+        if self.is_pinned(index) {
+            self.state.remove(&key);
+        } else {
+            self.state.set(&key, "true");
+        }
+        self.update_line_items(self.time);
+
+        if let Err(err) = self.state.save() {
+            self.message = Some(format!("Error: Could not save the pin: {err}"));
+        }
+    }
+
+    fn toggle_privacy(&mut self) {
+        self.private = !self.private;
+        self.revealed = None;
+        self.update_line_items(self.time);
+    }
+
+    // Only the selected entry is revealed, it is masked again when another line is selected.
+    fn reveal(&mut self) {
+        if self.private {
+            self.revealed = self
+                .selected_index()
+                .map(|index| (index, self.time + REVEAL_DURATION));
+        }
+    }
+
+    fn is_masked(&self, line_index: usize) -> bool {
+        let is_revealed = line_index == self.list_view.current_index
+            && matches!(
+                (self.selected_index(), self.revealed),
+                (Some(index), Some((revealed_index, until))) if index == revealed_index && self.time < until
+            );
+
+        self.private && !is_revealed
+    }
+
+    fn toggle_group(&mut self, group: &str) {
+        if self.collapsed_groups.iter().any(|x| x == group) {
+            self.collapsed_groups.retain(|x| x != group);
+        } else {
+            self.collapsed_groups.push(group.to_string());
+        }
+        self.update_line_items(self.time);
+    }
+
+    fn set_tag_filter(&mut self, tag_filter: Option<String>) {
+        self.tag_filter = tag_filter;
+        self.update_line_items(self.time);
+    }
+
+    fn toggle_sort_mode(&mut self) {
+        let (sort_mode, value) = match self.sort_mode {
+            SortMode::File => (SortMode::Frecency, "frecency"),
+            SortMode::Frecency => (SortMode::File, "file"),
+        };
+        self.sort_mode = sort_mode;
+        self.update_line_items(self.time);
+
+        self.state.set(SORT_STATE_KEY, value);
+        if let Err(err) = self.state.save() {
+            self.message = Some(format!("Error: Could not save the sort mode: {err}"));
+        }
+    }
+
+    // Headers collapse or expand their group, TOTPs are copied.
+    fn select(&mut self) {
+        match self.selected_row().cloned() {
+            Some(Row::Header(group)) => self.toggle_group(&group),
+            Some(Row::Entry(index)) => {
+                self.list_view.mark_selected_line_item();
+                (self.copy_code)(&self.totps[index]);
+                self.record_usage();
+            }
+            None => {}
+        }
+    }
+
+    // The list is not sorted again right away, that would move the entry away from under the cursor.
+    // Clicking a row selects it, double clicking copies the code the same as the copy action.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.list_view.select_next(),
+            MouseEventKind::ScrollUp => self.list_view.select_prev(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(index) = self.line_item_positions.iter().position(|position| {
+                    position.is_some_and(|(column, row)| {
+                        row == mouse.row
+                            && mouse.column >= column
+                            && mouse.column - column < self.column_width
+                    })
+                }) else {
+                    return;
+                };
+                self.list_view.current_index = index;
+
+                let now = Instant::now();
+                let is_double_click = self.last_click.is_some_and(|(last_index, time)| {
+                    last_index == index && now.duration_since(time) <= DOUBLE_CLICK_INTERVAL
+                });
+
+                if is_double_click {
+                    self.last_click = None;
+                    self.select();
+                } else {
+                    self.last_click = Some((index, now));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Moves to the next line matching the last search, `count` times.
+    fn select_next_match(&mut self, forward: bool, count: usize) {
+        let Some(search) = &self.last_search else {
+            self.message = Some("No previous search.".to_string());
+            return;
+        };
+
+        for _ in 0..count {
+            if !self
+                .list_view
+                .select_next_matching(forward, |line_item| matches_search(line_item, search))
+            {
+                self.message = Some(format!("No match for '{search}'."));
+                return;
+            }
+        }
+    }
+
+    // Keys typed while a command is shown edit, submit or cancel it.
+    fn handle_command_event(&mut self, event: &Event) {
+        let Some(command) = &mut self.command else {
+            return;
+        };
+
+        if let TotpCommandType::Delete(_) = command.command_type {
+            // Any other key than 'y' cancels the deletion.
+            self.command = None;
+            if event == &Event::Key(KeyCode::Char('y').into()) {
+                self.delete_selected();
+            }
+        } else if event == &Event::Key(KeyCode::Backspace.into()) {
+            let was_tag_search = command.input.starts_with('#');
+            command.input.pop();
+            // The tag filter follows the input, and is removed together with the '#'.
+            if let (TotpCommandType::Search, true) = (&command.command_type, was_tag_search) {
+                let tag_filter = command.input.strip_prefix('#').map(ToString::to_string);
+                self.set_tag_filter(tag_filter);
+            }
+        } else if event == &Event::Key(KeyCode::Esc.into()) {
+            // Cancelling a search for a tag also removes the tag filter.
+            if command.input.starts_with('#') {
+                self.set_tag_filter(None);
+            }
+            self.command = None;
+        } else if event == &Event::Key(KeyCode::Enter.into()) {
+            if let Some(command) = self.command.take() {
+                self.submit_command(command);
+            }
+        } else if let Event::Key(KeyEvent {
+            code: KeyCode::Char(char),
+            modifiers: _,
+            kind: _,
+            state: _,
+        }) = event
+        {
+            command.input.push(*char);
+            if let TotpCommandType::Search = command.command_type {
+                // Searching for '#tag' only lists the TOTPs with the tag.
+                if let Some(tag_filter) = command.input.strip_prefix('#') {
+                    let tag_filter = tag_filter.to_string();
+                    self.set_tag_filter(Some(tag_filter));
+                } else if let Some(match_index) =
+                    find_line_item_matching_search(&self.list_view.line_items, &command.input)
+                {
+                    self.list_view.current_index = match_index;
+                }
+            }
+        }
+    }
+
+    fn record_usage(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+
+        let now = unix_seconds(self.time);
+        frecency::record_usage(&mut self.state, &self.totps[index], now);
+
+        if let Err(err) = self.state.save() {
+            self.message = Some(format!("Error: Could not save the usage: {err}"));
+        }
+    }
+
+    fn submit_command(&mut self, command: TotpCommand) {
+        let result = match command.command_type {
+            TotpCommandType::Search => {
+                if !command.input.is_empty() && !command.input.starts_with('#') {
+                    self.last_search = Some(command.input);
+                }
+                return;
+            }
+            TotpCommandType::Delete(_) => return,
+            TotpCommandType::Add if command.input.to_lowercase().starts_with("otpauth://") => {
+                config::add_entry(
+                    &self.config_file_path,
+                    &command.input,
+                    self.digits,
+                    self.interval,
+                )
+            }
+            TotpCommandType::Add => {
+                if command.input.is_empty() {
+                    return;
+                }
+                // The name is checked before the secret is typed.
+                match config::validate_name(&command.input) {
+                    Ok(()) => {
+                        self.command =
+                            Some(TotpCommand::new(TotpCommandType::AddSecret(command.input)));
+                    }
+                    Err(err) => self.message = Some(format!("Error: {err}")),
+                }
+                return;
+            }
+            TotpCommandType::AddSecret(name) => {
+                // Secrets are often shown in groups separated by spaces.
+                let secret = Zeroizing::new(command.input.split_whitespace().collect::<String>());
+                config::create_uri_string_format(&name, &secret)
+                    .map_err(Into::into)
+                    .and_then(|uri| {
+                        config::add_entry(&self.config_file_path, &uri, self.digits, self.interval)
+                    })
+            }
+            TotpCommandType::Rename(_) => match self.selected_index() {
+                Some(index) => config::rename_entry(
+                    &self.config_file_path,
+                    index,
+                    &command.input,
+                    self.digits,
+                    self.interval,
+                ),
+                None => return,
+            },
+        };
+
+        self.update_totps(result);
+    }
+
+    fn delete_selected(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+
+        // An empty list can not be displayed, so the last entry has to be removed by hand.
+        if self.totps.len() == 1 {
+            self.message = Some("Can not delete the last entry.".to_string());
+            return;
+        }
+
+        let result =
+            config::delete_entry(&self.config_file_path, index, self.digits, self.interval);
+
+        self.update_totps(result);
+    }
+
+    fn update_totps(&mut self, result: Result<Vec<Totp>, Box<dyn Error>>) {
+        match result {
+            Ok(totps) => {
+                let added = totps.len() > self.totps.len();
+                self.totps = totps;
+                self.update_line_items(self.time);
+                if added {
+                    // The new entry is appended to the secrets file.
+                    let added_index = self.totps.len() - 1;
+                    if let Some(position) = self.list_view.line_items.iter().position(
+                        |x| matches!(x.value(), Row::Entry(index) if *index == added_index),
+                    ) {
+                        self.list_view.current_index = position;
+                    }
+                }
+            }
+            Err(err) => self.message = Some(format!("Error: {err}")),
         }
     }
 }
@@ -118,96 +870,237 @@ impl TotpListView {
 impl Element for TotpListView {}
 
 impl Display for TotpListView {
-    fn display(&mut self, w: &mut Stdout) -> Result<(), Box<dyn Error>> {
-        queue!(w, Clear(ClearType::FromCursorDown))?;
+    fn display(&mut self, buffer: &mut Buffer, area: Rect) {
+        let list_rows = area.height.saturating_sub(STATUS_ROWS).max(1);
+        let names = self
+            .totps
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        // Masked codes have a space between their halves.
+        let code_width = self.totps.iter().map(|x| x.digits).max().unwrap_or(0) as usize
+            + usize::from(self.private);
+        let layout = list_layout(
+            area.width,
+            longest_string(&names).unwrap_or(0),
+            code_width,
+            list_rows,
+        );
 
-        for (index, line) in self.list_view.line_items.iter_mut().enumerate() {
-            if index == self.list_view.current_index {
-                queue!(w, style::PrintStyledContent(line.text().blue()))?;
-            } else {
-                queue!(w, style::Print(line.text()))?;
+        let (positions, divider_position) = line_item_positions(
+            self.list_view.line_items.len(),
+            self.pinned_count,
+            layout.rows_per_column,
+        );
+        let row_count = positions
+            .iter()
+            .chain(&divider_position)
+            .map(|(_, row)| row.saturating_add(1))
+            .max()
+            .unwrap_or(0);
+        let selected_row = positions
+            .get(self.list_view.current_index)
+            .map_or(0, |(_, row)| *row);
+        let offset = scroll_offset(self.scroll_offset, selected_row, row_count, list_rows);
+        let column_width = u16::try_from(layout.column_width).unwrap_or(u16::MAX);
+        // The position in the view, `None` when the row is scrolled out of view.
+        let view_position = |(column, row): Position| {
+            (row >= offset && row - offset < list_rows)
+                .then(|| (column.saturating_mul(column_width), row - offset))
+        };
+
+        self.scroll_offset = offset;
+        self.column_width = column_width;
+        self.line_item_positions = positions.iter().map(|x| view_position(*x)).collect();
+        self.page_size = usize::from(list_rows);
+
+        for (index, line) in self.list_view.line_items.iter().enumerate() {
+            let Some((column, row)) = self.line_item_positions[index] else {
+                continue;
             };
+            buffer.move_to(area.x.saturating_add(column), area.y.saturating_add(row));
+
+            // The most used entries are shown in bold, so they are easy to spot.
+            let is_frequent =
+                matches!(line.value(), Row::Entry(index) if self.frequent.contains(index));
+            for (part, style) in styled_line_parts(
+                line_parts(line, &self.totps, self.time, &layout, self.is_masked(index)),
+                line.value(),
+                index == self.list_view.current_index,
+                is_frequent,
+                &self.theme,
+            ) {
+                buffer.print_styled(&part, style);
+            }
 
             if line.is_marked() {
                 // When the line is marked we want to indicate it with a '*'.
-                queue!(w, style::Print(" *".blue()))?;
+                buffer.print_styled(" *", self.theme.marked);
             }
+        }
 
-            queue!(w, cursor::MoveToNextLine(1))?;
+        // The pinned section is separated from the rest of the entries.
+        if let Some((column, row)) = divider_position.and_then(view_position) {
+            buffer.move_to(area.x.saturating_add(column), area.y.saturating_add(row));
+            let divider_width = if layout.compact {
+                layout.column_width.saturating_sub(MARK_WIDTH)
+            } else {
+                layout.name_width + SEPARATOR.len() + code_width
+            };
+            buffer.print(&"-".repeat(divider_width));
         }
 
-        match &self.command {
-            Some(command) => {
-                queue!(w, cursor::MoveToNextLine(1))?;
-                queue!(w, style::Print("/".to_string() + &command.input))?;
-            }
-            None => {}
+        // The status lines are below the list, after an empty row,
+        // the list leaves room for them when it does not fit.
+        let used_rows = row_count.saturating_sub(offset).min(list_rows);
+        buffer.move_to(area.x, area.y.saturating_add(used_rows));
+
+        if let Some(command) = &self.command {
+            buffer.next_line();
+            buffer.print_styled(&command.prompt(), self.theme.search_prompt);
+        } else if let Some(tag_filter) = &self.tag_filter {
+            buffer.next_line();
+            buffer.print_styled(
+                &format!("Tag: #{tag_filter} (Esc to clear)"),
+                self.theme.search_prompt,
+            );
         }
 
-        Ok(())
+        if let Some(message) = &self.message {
+            buffer.next_line();
+            buffer.print_styled(message, self.theme.error);
+        }
     }
 }
 
 impl HandleEvent for TotpListView {
-    fn handle_event(&mut self, event: &Event) {
-        match &mut self.command {
-            Some(command) => match command.command_type {
-                TotpCommandType::Search => {
-                    if event == &Event::Key(KeyCode::Backspace.into()) {
-                        command.input.pop();
-                    } else if event == &Event::Key(KeyCode::Enter.into())
-                        || event == &Event::Key(KeyCode::Esc.into())
-                    {
-                        self.command = None;
-                    } else if let Event::Key(KeyEvent {
-                        code: KeyCode::Char(char),
-                        modifiers: _,
-                        kind: _,
-                        state: _,
-                    }) = event
-                    {
-                        command.input.push(*char);
-                        if let Some(match_index) = find_line_item_matching_search(
-                            &self.list_view.line_items,
-                            &command.input,
-                        ) {
-                            self.list_view.current_index = match_index;
-                        };
-                    }
-                }
-            },
-            _ => {
-                if event == &Event::Key(KeyCode::Char('j').into()) {
-                    self.list_view.select_next();
-                } else if event == &Event::Key(KeyCode::Char('k').into()) {
-                    self.list_view.select_prev();
-                } else if event == &Event::Key(KeyCode::Enter.into()) {
-                    self.list_view.mark_selected_line_item();
-                    (self.list_view.selected_callback)(
-                        self.list_view.get_selected_line_item().value(),
-                    );
-                } else if event == &Event::Key(KeyCode::Char('/').into()) {
-                    self.command = Some(TotpCommand::new(TotpCommandType::Search));
-                }
+    fn handle_event(&mut self, event: &Event, action: Option<CountedAction>) {
+        if let Event::Key(_) = event {
+            self.message = None;
+        }
+
+        // Mouse events are ignored while a command is typed.
+        if let Event::Mouse(mouse) = event {
+            if self.command.is_none() {
+                self.handle_mouse(*mouse);
+            }
+            return;
+        }
+
+        // The count typed before the keys, moves without a count move a single line.
+        let line = action.and_then(|x| x.count);
+        let count = action.map_or(1, |x| x.count_or_one());
+
+        if self.command.is_some() {
+            self.handle_command_event(event);
+            return;
+        }
+
+        match action.map(|x| x.action) {
+            Some(Action::MoveDown) => (0..count).for_each(|_| self.list_view.select_next()),
+            Some(Action::MoveUp) => (0..count).for_each(|_| self.list_view.select_prev()),
+            Some(Action::PageDown) => {
+                self.list_view
+                    .select_next_by(self.page_size.saturating_mul(count));
+            }
+            Some(Action::PageUp) => {
+                self.list_view
+                    .select_prev_by(self.page_size.saturating_mul(count));
+            }
+            Some(Action::HalfPageDown) => {
+                self.list_view
+                    .select_next_by(self.half_page_size().saturating_mul(count));
             }
+            Some(Action::HalfPageUp) => {
+                self.list_view
+                    .select_prev_by(self.half_page_size().saturating_mul(count));
+            }
+            // With a count, both move to the line of the count like in vim.
+            Some(Action::Top | Action::Bottom) if line.is_some() => {
+                self.list_view.select_first();
+                self.list_view
+                    .select_next_by(line.unwrap_or(1).saturating_sub(1));
+            }
+            Some(Action::Top) => self.list_view.select_first(),
+            Some(Action::Bottom) => self.list_view.select_last(),
+            Some(Action::NextMatch) => self.select_next_match(true, count),
+            Some(Action::PreviousMatch) => self.select_next_match(false, count),
+            Some(Action::Copy) => self.select(),
+            Some(Action::ClearFilter) => self.set_tag_filter(None),
+            Some(Action::Pin) => self.toggle_pin(),
+            Some(Action::Sort) => self.toggle_sort_mode(),
+            Some(Action::TogglePrivacy) => self.toggle_privacy(),
+            Some(Action::Reveal) => self.reveal(),
+            Some(Action::Search) => {
+                self.command = Some(TotpCommand::new(TotpCommandType::Search));
+            }
+            Some(Action::Add) => self.command = Some(TotpCommand::new(TotpCommandType::Add)),
+            Some(Action::Rename) => {
+                self.command = self
+                    .selected_name()
+                    .map(|name| TotpCommand::new(TotpCommandType::Rename(name)));
+            }
+            Some(Action::Delete) => {
+                self.command = self
+                    .selected_name()
+                    .map(|name| TotpCommand::new(TotpCommandType::Delete(name)));
+            }
+            Some(Action::Help | Action::Quit) | None => {}
+        }
+    }
+
+    fn captures_input(&self) -> bool {
+        self.command.is_some()
+    }
+}
+
+// The TOTPs are loaded from the secrets file again when unlocking, the same as the agent does.
+impl Lock for TotpListView {
+    fn lock(&mut self) {
+        self.totps.clear();
+        // The line items show the names and codes and refer to the TOTPs by index,
+        // the selection is kept for when they are created again.
+        self.list_view.line_items.clear();
+        self.counters.clear();
+        // A secret might be typed in the prompt.
+        self.command = None;
+        self.revealed = None;
+        self.message = None;
+    }
+
+    fn unlock(&mut self) -> Result<(), Box<dyn Error>> {
+        let totps = config::load_totps(&self.config_file_path, self.digits, self.interval)?;
+        if totps.is_empty() {
+            return Err(config::TotpSecretFileError::InvalidFormat(
+                "The secrets file has no entries.".to_string(),
+            )
+            .into());
         }
+
+        self.totps = totps;
+        self.update_line_items(self.time);
+
+        Ok(())
     }
 }
 
 impl Refresh for TotpListView {
-    fn refresh(&mut self) {
-        let now = SystemTime::now();
-        let duration_used = totp::duration_used(self.interval, now);
-        // The refreshed flag is set to avoid an issue where on duration 0,
-        // the view is refreshed multiple times when interacting with the TUI.
-        if !self.refreshed && duration_used == 0 {
-            self.list_view
-                .set_line_items(create_line_items(&self.totps, now));
-            self.refreshed = true;
-        } else if self.refreshed {
-            self.refreshed = false;
+    fn refresh(&mut self, now: SystemTime) {
+        self.time = now;
+        if counters(&self.totps, now) != self.counters {
+            self.update_line_items(now);
         }
     }
+
+    // The codes change when the next interval of any TOTP starts,
+    // a revealed code is masked again when its time is up.
+    fn next_refresh(&self, time: SystemTime) -> Option<SystemTime> {
+        self.totps
+            .iter()
+            .map(|totp| totp::next_rollover(totp.interval, time))
+            .chain(self.revealed.map(|(_, until)| until).filter(|x| *x > time))
+            .min()
+    }
 }
 
 #[cfg(test)]
@@ -256,8 +1149,244 @@ mod tests {
         for (expected, input) in assertions {
             assert_eq!(
                 expected,
-                format_totp(&input, march_14_2020, name_max_length)
+                format_totp(&input, march_14_2020, name_max_length, false)
+            );
+        }
+
+        assert_eq!(
+            "Acme Inc.         | ••• •••",
+            format_totp(
+                &Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30),
+                march_14_2020,
+                name_max_length,
+                true
+            )
+        );
+    }
+
+    fn grouped_totps() -> Vec<Totp> {
+        vec![
+            Totp::new("Acme Inc.", "GZMWV5JLOMNI2XJL", 6, 30).with_group(Some("Work")),
+            Totp::new("Widget Co", "MFRGGZD", 6, 30),
+            Totp::new("Gizmo Corporation", "MFRGGZDF", 6, 30).with_group(Some("Personal")),
+            Totp::new("Foo Industries", "MZXW6YTBOI", 6, 30).with_group(Some("Work")),
+        ]
+    }
+
+    fn row_names<'a>(rows: &'a [Row], totps: &'a [Totp]) -> Vec<&'a str> {
+        rows.iter()
+            .map(|row| match row {
+                Row::Header(group) => group.as_str(),
+                Row::Entry(index) => totps[*index].name.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn selected_line_is_styled_as_a_whole() {
+        let theme = Theme::monochrome();
+        let row = Row::Entry(0);
+        let parts = || {
+            vec![
+                ("Acme Inc.".to_string(), Part::Name),
+                (SEPARATOR.to_string(), Part::Text),
+                ("123456".to_string(), Part::Code),
+            ]
+        };
+
+        let styled_parts = styled_line_parts(parts(), &row, false, true, &theme);
+        assert!(styled_parts
+            .iter()
+            .all(|(_, style)| style.attributes.has(Attribute::Bold)));
+
+        let styled_parts = styled_line_parts(parts(), &row, true, false, &theme);
+        assert!(styled_parts
+            .iter()
+            .all(|(_, style)| *style == theme.selected));
+        assert!(theme.selected.attributes.has(Attribute::Reverse));
+    }
+
+    #[test]
+    fn divider_after_pinned_rows_takes_a_row() {
+        assert_eq!(
+            (vec![(0, 0), (0, 1), (0, 3), (0, 4)], Some((0, 2))),
+            line_item_positions(4, 2, u16::MAX)
+        );
+        // Without pinned rows, or when every row is pinned, there is no divider.
+        assert_eq!(
+            (vec![(0, 0), (0, 1), (0, 2)], None),
+            line_item_positions(3, 0, u16::MAX)
+        );
+        assert_eq!(
+            (vec![(0, 0), (0, 1), (0, 2)], None),
+            line_item_positions(3, 3, u16::MAX)
+        );
+    }
+
+    #[test]
+    fn line_items_continue_in_the_next_column() {
+        assert_eq!(
+            (vec![(0, 0), (1, 0), (1, 1)], Some((0, 1))),
+            line_item_positions(3, 1, 2)
+        );
+    }
+
+    #[test]
+    fn list_scrolls_only_as_far_as_needed_to_show_the_row() {
+        let assertions = [
+            // The row is in view already.
+            (0, 4, 0),
+            (5, 9, 5),
+            // Scrolling down, the row ends up at the bottom.
+            (0, 5, 1),
+            (0, 19, 15),
+            // Scrolling up, the row ends up at the top.
+            (10, 7, 7),
+            // The list got shorter, so it does not leave empty rows at the bottom.
+            (18, 19, 15),
+        ];
+
+        for (offset, row, expected) in assertions {
+            assert_eq!(
+                expected,
+                scroll_offset(offset, row, 20, 5),
+                "{offset} {row}"
             );
         }
+        assert_eq!(0, scroll_offset(3, 2, 3, 5));
+    }
+
+    #[test]
+    fn layout_fits_the_width() {
+        // Long names are cut off, narrow terminals show the code first.
+        assert_eq!(
+            ListLayout {
+                compact: false,
+                name_width: 30,
+                column_width: 41,
+                rows_per_column: u16::MAX
+            },
+            list_layout(41, 40, 6, 10)
+        );
+        assert_eq!(
+            ListLayout {
+                compact: true,
+                name_width: 12,
+                column_width: 21,
+                rows_per_column: u16::MAX
+            },
+            list_layout(21, 40, 6, 10)
+        );
+        // Wide terminals show a grid with columns as wide as the longest line.
+        assert_eq!(
+            ListLayout {
+                compact: false,
+                name_width: 20,
+                column_width: 35,
+                rows_per_column: 10
+            },
+            list_layout(140, 20, 6, 10)
+        );
+    }
+
+    #[test]
+    fn codes_line_up_after_wide_and_combining_names() {
+        let time = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_400, 0);
+        let names = ["日本銀行", "Cafe\u{301}", "Acme Inc."];
+        let layout = list_layout(40, longest_string(&names).unwrap(), 6, 10);
+        let line_text = |name: &str| {
+            let totps = [Totp::new(name, "GZMWV5JLOMNI2XJL", 6, 30)];
+            line_parts(
+                &LineItem::new(name, Row::Entry(0)),
+                &totps,
+                time,
+                &layout,
+                false,
+            )
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect::<String>()
+        };
+
+        assert_eq!("日本銀行  | 396507", line_text(names[0]));
+        assert_eq!("Cafe\u{301}      | 396507", line_text(names[1]));
+        assert_eq!("Acme Inc. | 396507", line_text(names[2]));
+
+        // Wide characters are not split when the name is cut off.
+        let layout = list_layout(16, 8, 6, 10);
+        assert_eq!(7, layout.name_width);
+        let totps = [Totp::new(names[0], "GZMWV5JLOMNI2XJL", 6, 30)];
+        let parts = line_parts(
+            &LineItem::new(names[0], Row::Entry(0)),
+            &totps,
+            time,
+            &layout,
+            false,
+        );
+        assert_eq!("日本銀…", parts[2].0);
+    }
+
+    #[test]
+    fn rows_are_grouped_under_headers() {
+        let totps = grouped_totps();
+
+        let (rows, pinned_count) = create_rows(&totps, &[0, 1, 2, 3], |_| false, &[]);
+
+        assert_eq!(0, pinned_count);
+        assert_eq!(
+            vec![
+                "Widget Co",
+                "Work",
+                "Acme Inc.",
+                "Foo Industries",
+                "Personal",
+                "Gizmo Corporation"
+            ],
+            row_names(&rows, &totps)
+        );
+    }
+
+    #[test]
+    fn pinned_rows_come_first_and_collapsed_groups_are_hidden() {
+        let totps = grouped_totps();
+
+        let (rows, pinned_count) = create_rows(
+            &totps,
+            &[3, 2, 1, 0],
+            |index| index == 0,
+            &["Personal".to_string()],
+        );
+
+        assert_eq!(1, pinned_count);
+        assert_eq!(
+            vec![
+                "Acme Inc.",
+                "Widget Co",
+                "Work",
+                "Foo Industries",
+                "Personal"
+            ],
+            row_names(&rows, &totps)
+        );
+    }
+
+    #[test]
+    fn secret_is_masked_in_prompt() {
+        let mut command = TotpCommand::new(TotpCommandType::AddSecret("Acme Inc.".to_string()));
+        command.input = "GZMWV5JL".to_string();
+
+        assert_eq!("Secret for 'Acme Inc.': ********", command.prompt());
+
+        let mut command = TotpCommand::new(TotpCommandType::Add);
+        command.input = "otpauth://totp/Acme:Acme?Secret=GZMWV5JL&issuer=Acme".to_string();
+        assert_eq!(
+            "Add (otpauth URI or name): otpauth://totp/Acme:Acme?Secret=********&issuer=Acme",
+            command.prompt()
+        );
+        command.input = "otpauth://totp/Acme:Acme?secret=GZMW".to_string();
+        assert_eq!(
+            "Add (otpauth URI or name): otpauth://totp/Acme:Acme?secret=****",
+            command.prompt()
+        );
     }
 }
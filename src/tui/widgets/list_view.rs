@@ -1,20 +1,20 @@
 pub struct ListView<T> {
     pub line_items: Vec<LineItem<T>>,
     pub current_index: usize,
-    pub selected_callback: Box<dyn Fn(&T)>,
 }
 
 impl<T> ListView<T> {
-    pub fn new(line_items: Vec<LineItem<T>>, selected_callback: Box<dyn Fn(&T)>) -> Self {
+    pub fn new(line_items: Vec<LineItem<T>>) -> Self {
         Self {
             line_items,
             current_index: 0,
-            selected_callback,
         }
     }
 
     pub fn set_line_items(&mut self, line_items: Vec<LineItem<T>>) {
         self.line_items = line_items;
+        // The new line items might be fewer than the old ones.
+        self.current_index = self.current_index.min(self.max_index());
     }
 
     pub fn select_next(&mut self) {
@@ -25,17 +25,63 @@ impl<T> ListView<T> {
     }
 
     pub fn select_prev(&mut self) {
-        // Mark both the old and the new selected index to make sure they're redrawn.
         self.current_index = self.current_index.saturating_sub(1);
     }
 
+    pub fn select_next_by(&mut self, count: usize) {
+        self.current_index = self
+            .current_index
+            .saturating_add(count)
+            .min(self.max_index());
+    }
+
+    pub fn select_prev_by(&mut self, count: usize) {
+        self.current_index = self.current_index.saturating_sub(count);
+    }
+
+    pub fn select_first(&mut self) {
+        self.current_index = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.current_index = self.max_index();
+    }
+
+    /// Selects the next line item matching the predicate, or the previous one when not `forward`.
+    /// The search wraps around the end of the list. Returns false when no line item matches.
+    pub fn select_next_matching(
+        &mut self,
+        forward: bool,
+        predicate: impl Fn(&LineItem<T>) -> bool,
+    ) -> bool {
+        let length = self.line_items.len();
+        let found_index = (1..=length)
+            .map(|step| {
+                if forward {
+                    (self.current_index + step) % length
+                } else {
+                    (self.current_index + length - step) % length
+                }
+            })
+            .find(|index| predicate(&self.line_items[*index]));
+
+        if let Some(index) = found_index {
+            self.current_index = index;
+        }
+
+        found_index.is_some()
+    }
+
     pub fn mark_selected_line_item(&mut self) {
         self.unmark_all_line_items();
-        self.line_items[self.current_index].mark();
+        if let Some(line_item) = self.line_items.get_mut(self.current_index) {
+            line_item.mark();
+        }
     }
 
-    pub fn get_selected_line_item(&self) -> &LineItem<T> {
-        &self.line_items[self.current_index]
+    // There is no selected line item when the list is empty, for example when nothing matches a filter.
+    pub fn get_selected_line_item(&self) -> Option<&LineItem<T>> {
+        self.line_items.get(self.current_index)
     }
 
     fn unmark_all_line_items(&mut self) {
@@ -93,14 +139,11 @@ mod tests {
 
     #[test]
     fn _initialiation_of_list_view_correct_state() {
-        let list_view: ListView<i32> = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let list_view: ListView<i32> = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         // Checks that the initial line items are not being modified on initialization.
         assert_eq!(list_view.line_items[0].text, "First");
@@ -116,14 +159,11 @@ mod tests {
 
     #[test]
     fn can_set_new_line_items() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         list_view.set_line_items(vec![
             LineItem::new("NewFirst", 11),
@@ -140,16 +180,40 @@ mod tests {
         assert_eq!(list_view.line_items[2].value, 33);
     }
 
+    #[test]
+    fn selected_index_is_kept_within_new_line_items() {
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
+
+        list_view.select_next();
+        list_view.select_next();
+
+        list_view.set_line_items(vec![LineItem::new("NewFirst", 11)]);
+
+        // The selected index was at the end of the list, that is no longer there.
+        assert_eq!(list_view.current_index, 0);
+    }
+
+    #[test]
+    fn empty_list_has_no_selected_line_item() {
+        let mut list_view: ListView<i32> = ListView::new(vec![]);
+
+        list_view.select_next();
+        list_view.mark_selected_line_item();
+
+        assert!(list_view.get_selected_line_item().is_none());
+    }
+
     #[test]
     fn can_select_next() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         list_view.select_next();
 
@@ -160,14 +224,11 @@ mod tests {
 
     #[test]
     fn can_select_previous() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         // Increase the selected item multiple times to make sure,
         // so we can make sure that the selected index has changed.
@@ -184,14 +245,11 @@ mod tests {
 
     #[test]
     fn when_at_the_end_of_index_do_not_increment_on_select_next() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         // Increment until we are at the end of the list.
         list_view.select_next();
@@ -207,14 +265,11 @@ mod tests {
 
     #[test]
     fn when_at_the_start_of_the_list_do_not_decrement_on_select_prev() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         // Select previous to validate that the selected index do not decrement.
         list_view.select_prev();
@@ -223,21 +278,62 @@ mod tests {
         assert_eq!(list_view.current_index, 0);
     }
 
+    #[test]
+    fn can_select_by_count_and_jump_to_first_and_last() {
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
+
+        // Moving past the end of the list stops at the last line item.
+        list_view.select_next_by(10);
+        assert_eq!(list_view.current_index, 2);
+
+        list_view.select_prev_by(1);
+        assert_eq!(list_view.current_index, 1);
+
+        list_view.select_first();
+        assert_eq!(list_view.current_index, 0);
+
+        list_view.select_last();
+        assert_eq!(list_view.current_index, 2);
+    }
+
+    #[test]
+    fn can_select_next_matching_line_item_with_wrap_around() {
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
+        let is_odd = |line_item: &LineItem<i32>| line_item.value() % 2 == 1;
+
+        assert!(list_view.select_next_matching(true, is_odd));
+        assert_eq!(list_view.current_index, 2);
+
+        assert!(list_view.select_next_matching(true, is_odd));
+        assert_eq!(list_view.current_index, 0);
+
+        assert!(list_view.select_next_matching(false, is_odd));
+        assert_eq!(list_view.current_index, 2);
+
+        assert!(!list_view.select_next_matching(true, |line_item| *line_item.value() > 3));
+        assert_eq!(list_view.current_index, 2);
+    }
+
     #[test]
     fn can_mark_the_selected_line_item() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         list_view.mark_selected_line_item();
 
         // Makes sure that the item has been marked.
-        assert!(list_view.get_selected_line_item().is_marked());
+        assert!(list_view.get_selected_line_item().unwrap().is_marked());
 
         // Make sure that other items are not marked.
         // Only one item in the list be marked at a time.
@@ -247,20 +343,17 @@ mod tests {
 
     #[test]
     fn can_unmark_the_selected_line_item() {
-        let mut list_view = ListView::new(
-            vec![
-                LineItem::new("First", 1),
-                LineItem::new("Second", 2),
-                LineItem::new("Third", 3),
-            ],
-            Box::new(|_| {}),
-        );
+        let mut list_view = ListView::new(vec![
+            LineItem::new("First", 1),
+            LineItem::new("Second", 2),
+            LineItem::new("Third", 3),
+        ]);
 
         // We mark so that we can assert that it has been unmarked after.
         list_view.mark_selected_line_item();
 
         // Makes sure that the item has been marked.
-        assert!(list_view.get_selected_line_item().is_marked());
+        assert!(list_view.get_selected_line_item().unwrap().is_marked());
 
         // Unmark all items.
         list_view.unmark_all_line_items();
@@ -0,0 +1,118 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use serde_json::{json, Value};
+
+use crate::totp;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum WatchFormat {
+    // JSON for waybar custom modules with 'return-type' set to 'json'.
+    Waybar,
+    // Plain text for polybar and i3blocks, which both show the last line printed.
+    Plain,
+}
+
+// The period of the entry, entries always have one, the default is only there to avoid dividing by zero.
+fn period(entry: &Value) -> u64 {
+    entry["period"].as_u64().filter(|x| *x > 0).unwrap_or(30)
+}
+
+/// Formats the JSON entry of a TOTP, the same entry the `code --json` command prints,
+/// the countdown is calculated for the given time.
+pub fn format_line(
+    entry: &Value,
+    time: SystemTime,
+    format: WatchFormat,
+    expiring_seconds: u64,
+) -> String {
+    let code = entry["code"].as_str().unwrap_or_default();
+    let period = period(entry);
+    let remaining = totp::seconds_remaining(period, time);
+    let text = format!("{code} ({remaining}s)");
+
+    match format {
+        WatchFormat::Waybar => json!({
+            "text": text,
+            "tooltip": entry["name"],
+            "class": if remaining <= expiring_seconds { "expiring" } else { "normal" },
+            "percentage": remaining * 100 / period,
+        })
+        .to_string(),
+        WatchFormat::Plain => text,
+    }
+}
+
+/// Writes a line every second until writing fails, which happens when the status bar exits.
+/// The entry is loaded again when its code has expired.
+pub fn watch(
+    w: &mut impl Write,
+    mut load_entry: impl FnMut() -> Value,
+    format: WatchFormat,
+    expiring_seconds: u64,
+) -> io::Result<()> {
+    // The entry and the time its code expires.
+    let mut current: Option<(Value, SystemTime)> = None;
+
+    loop {
+        let now = SystemTime::now();
+        let entry = match current.take() {
+            Some((entry, expires)) if now < expires => entry,
+            _ => load_entry(),
+        };
+        writeln!(w, "{}", format_line(&entry, now, format, expiring_seconds))?;
+        w.flush()?;
+        let expires = totp::next_rollover(period(&entry), now);
+        current = Some((entry, expires));
+
+        // Sleeps until the start of the next second, that way the countdown does not drift
+        // and the new code is written as soon as the TOTP rolls over.
+        let subsec = now
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        thread::sleep(Duration::from_nanos(1_000_000_000 - u64::from(subsec)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{json, totp::Totp};
+
+    use super::*;
+
+    #[test]
+    fn line_is_formatted_correctly() {
+        let march_14_2020 = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_400, 0);
+        let totp = Totp::new("Gizmo Corporation", "MFRGGZDF", 6, 30);
+        let entry = json::totp_to_json(&totp, march_14_2020, false);
+
+        assert_eq!(
+            "711370 (30s)",
+            format_line(&entry, march_14_2020, WatchFormat::Plain, 5)
+        );
+        assert_eq!(
+            r#"{"class":"normal","percentage":100,"text":"711370 (30s)","tooltip":"Gizmo Corporation"}"#,
+            format_line(&entry, march_14_2020, WatchFormat::Waybar, 5)
+        );
+    }
+
+    #[test]
+    fn class_is_expiring_when_few_seconds_remain() {
+        let totp = Totp::new("Gizmo Corporation", "MFRGGZDF", 6, 30);
+
+        let assertions = [(24, "normal"), (25, "expiring"), (29, "expiring")];
+
+        for (seconds, expected) in assertions {
+            let time = SystemTime::UNIX_EPOCH + Duration::new(1_584_140_400 + seconds, 0);
+            let entry = json::totp_to_json(&totp, time, false);
+            let line: Value =
+                serde_json::from_str(&format_line(&entry, time, WatchFormat::Waybar, 5)).unwrap();
+
+            assert_eq!(expected, line["class"]);
+        }
+    }
+}